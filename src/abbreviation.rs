@@ -0,0 +1,55 @@
+/// Common words and the abbreviations they are sent as on the air. Words
+/// abbreviated to nothing are dropped.
+const ABBREVIATIONS: &[(&str, &str)] = &[
+    ("AND", "ES"),
+    ("THE", ""),
+    ("YOU", "U"),
+    ("YOUR", "UR"),
+    ("ARE", "R"),
+    ("FOR", "FER"),
+    ("PLEASE", "PSE"),
+    ("THANKS", "TNX"),
+    ("THANK", "TNX"),
+    ("REPORT", "RPT"),
+    ("WEATHER", "WX"),
+    ("NAME", "NM"),
+    ("HERE", "HR"),
+    ("THERE", "THR"),
+    ("GOOD", "GD"),
+    ("MORNING", "GM"),
+    ("AFTERNOON", "GA"),
+    ("EVENING", "GE"),
+    ("RECEIVED", "R"),
+    ("AGAIN", "AGN"),
+    ("ABOUT", "ABT"),
+    ("BEFORE", "B4"),
+    ("WITH", "W"),
+    ("WILL", "WL"),
+    ("ANTENNA", "ANT"),
+    ("POWER", "PWR"),
+    ("SORRY", "SRI"),
+    ("NUMBER", "NR"),
+    ("SIGNAL", "SIG"),
+    ("CONDITIONS", "CONDX"),
+    ("REGARDS", "RGDS"),
+    ("COPY", "CPY"),
+];
+
+/// Replaces the words of a text that have a common abbreviation with it.
+/// Words are matched as a whole and case-insensitively. The words of the
+/// result are separated by single spaces.
+pub fn abbreviate(text: &str) -> String {
+    text.split_whitespace()
+        .filter_map(|word| {
+            match ABBREVIATIONS
+                .iter()
+                .find(|(long, _)| long.eq_ignore_ascii_case(word))
+            {
+                Some((_, "")) => None,
+                Some((_, short)) => Some(*short),
+                None => Some(word),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
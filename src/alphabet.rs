@@ -0,0 +1,447 @@
+use std::str::FromStr;
+
+/// The character tables that text can be encoded with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Alphabet {
+    #[default]
+    Latin,
+    Greek,
+    Cyrillic,
+    Hebrew,
+}
+
+impl FromStr for Alphabet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Alphabet::*;
+
+        match s.to_lowercase().as_str() {
+            "latin" => Ok(Latin),
+            "greek" => Ok(Greek),
+            "cyrillic" => Ok(Cyrillic),
+            "hebrew" => Ok(Hebrew),
+            _ => Err(format!("unknown alphabet: {}", s)),
+        }
+    }
+}
+
+impl Alphabet {
+    /// The letters specific to this alphabet.
+    fn letters(self) -> &'static [(char, &'static str)] {
+        match self {
+            Alphabet::Latin => LATIN,
+            Alphabet::Greek => GREEK,
+            Alphabet::Cyrillic => CYRILLIC,
+            Alphabet::Hebrew => HEBREW,
+        }
+    }
+
+    /// Looks up the code of a single character. Letters are matched
+    /// case-insensitively and without their Greek accents. Digits and
+    /// punctuation are shared between all alphabets.
+    pub fn lookup(self, c: char) -> Option<&'static str> {
+        let c = without_accent(c.to_uppercase().next().unwrap_or(c));
+
+        self.letters()
+            .iter()
+            .chain(COMMON)
+            .find(|(letter, _)| *letter == c)
+            .map(|(_, code)| *code)
+    }
+
+    /// All characters and their codes.
+    pub fn entries(self) -> impl Iterator<Item = (char, &'static str)> {
+        self.letters().iter().chain(COMMON).copied()
+    }
+
+    /// The letters of this alphabet and the digits, which random groups are
+    /// drawn from by default.
+    pub fn alphanumeric(self) -> String {
+        self.entries()
+            .map(|(c, _)| c)
+            .filter(|c| c.is_alphanumeric())
+            .collect()
+    }
+
+    /// The characters whose codes are closest to `code` by edit distance,
+    /// nearest first.
+    pub fn suggest(self, code: &str, count: usize) -> Vec<(char, &'static str)> {
+        let mut entries = self
+            .entries()
+            .map(|entry| (edit_distance(code, entry.1), entry))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(distance, (_, code))| (*distance, code.len()));
+
+        entries
+            .into_iter()
+            .take(count)
+            .map(|(_, entry)| entry)
+            .collect()
+    }
+
+    /// Looks up the character of a single code, the reverse of
+    /// [`Alphabet::lookup`]. The first matching table entry wins.
+    pub fn decode(self, code: &str) -> Option<char> {
+        self.letters()
+            .iter()
+            .chain(COMMON)
+            .find(|(_, letter_code)| *letter_code == code)
+            .map(|(letter, _)| *letter)
+    }
+}
+
+/// Looks up the code of a prosign by its name, case-insensitively.
+pub fn prosign(name: &str) -> Option<&'static str> {
+    PROSIGNS
+        .iter()
+        .find(|(prosign, _, _)| prosign.eq_ignore_ascii_case(name))
+        .map(|(_, code, _)| *code)
+}
+
+/// Encodes the letters of a single word, each with the character it was
+/// encoded from. A prosign's name in angle brackets, such as `<SK>`, is sent
+/// as the prosign, which has no single character.
+fn encode_word(word: &str, alphabet: Alphabet) -> Vec<(Option<char>, &'static str)> {
+    let mut codes = Vec::new();
+    let mut rest = word;
+    while let Some(c) = rest.chars().next() {
+        let prosign_code = rest.strip_prefix('<').and_then(|inner| {
+            let (name, after) = inner.split_once('>')?;
+            Some((prosign(name)?, after))
+        });
+        match prosign_code {
+            Some((code, after)) => {
+                codes.push((None, code));
+                rest = after;
+            }
+            None => {
+                codes.extend(alphabet.lookup(c).map(|code| (Some(c), code)));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    codes
+}
+
+/// Encodes the words of a text into the codes of their letters, keeping the
+/// character each letter was encoded from. Characters without a code are
+/// dropped.
+pub fn encode_words(text: &str, alphabet: Alphabet) -> Vec<Vec<(Option<char>, &'static str)>> {
+    text.split_whitespace()
+        .map(|word| encode_word(word, alphabet))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// The characters of a text that have no code, with their offsets in
+/// characters. Whitespace and prosign names in angle brackets are known.
+pub fn unknown_chars(text: &str, alphabet: Alphabet) -> Vec<(usize, char)> {
+    let mut unknown = Vec::new();
+    let mut offset = 0;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let prosign_name = rest
+            .strip_prefix('<')
+            .and_then(|inner| inner.split_once('>'))
+            .map(|(name, _)| name)
+            .filter(|name| prosign(name).is_some());
+        if let Some(name) = prosign_name {
+            offset += name.chars().count() + 2;
+            rest = &rest[name.len() + 2..];
+            continue;
+        }
+
+        if !c.is_whitespace() && alphabet.lookup(c).is_none() {
+            unknown.push((offset, c));
+        }
+        offset += 1;
+        rest = &rest[c.len_utf8()..];
+    }
+
+    unknown
+}
+
+/// Encodes text into a dot/dash string. Letters are separated by a single
+/// space and words by a slash. Characters without a code are dropped.
+pub fn encode_text(text: &str, alphabet: Alphabet) -> String {
+    encode_words(text, alphabet)
+        .iter()
+        .map(|word| {
+            word.iter()
+                .map(|(_, code)| *code)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/// Decodes a dot/dash string into text, the reverse of [`encode_text`].
+/// Letters are separated by whitespace and words by a slash. Codes without
+/// a character are decoded as `?`.
+pub fn decode_code(code: &str, alphabet: Alphabet) -> String {
+    code.split('/')
+        .map(|word| {
+            word.split_whitespace()
+                .map(|letter| alphabet.decode(letter).unwrap_or('?'))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The letter of an uppercase Greek letter with a tonos or dialytika.
+/// Morse code has no accents, so they are dropped.
+fn without_accent(c: char) -> char {
+    match c {
+        'Ά' => 'Α',
+        'Έ' => 'Ε',
+        'Ή' => 'Η',
+        'Ί' | 'Ϊ' => 'Ι',
+        'Ό' => 'Ο',
+        'Ύ' | 'Ϋ' => 'Υ',
+        'Ώ' => 'Ω',
+        _ => c,
+    }
+}
+
+/// The Levenshtein distance between two codes.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a != *b);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+const LATIN: &[(char, &str)] = &[
+    ('A', ".-"),
+    ('B', "-..."),
+    ('C', "-.-."),
+    ('D', "-.."),
+    ('E', "."),
+    ('F', "..-."),
+    ('G', "--."),
+    ('H', "...."),
+    ('I', ".."),
+    ('J', ".---"),
+    ('K', "-.-"),
+    ('L', ".-.."),
+    ('M', "--"),
+    ('N', "-."),
+    ('O', "---"),
+    ('P', ".--."),
+    ('Q', "--.-"),
+    ('R', ".-."),
+    ('S', "..."),
+    ('T', "-"),
+    ('U', "..-"),
+    ('V', "...-"),
+    ('W', ".--"),
+    ('X', "-..-"),
+    ('Y', "-.--"),
+    ('Z', "--.."),
+];
+
+const GREEK: &[(char, &str)] = &[
+    ('Α', ".-"),
+    ('Β', "-..."),
+    ('Γ', "--."),
+    ('Δ', "-.."),
+    ('Ε', "."),
+    ('Ζ', "--.."),
+    ('Η', "...."),
+    ('Θ', "-.-."),
+    ('Ι', ".."),
+    ('Κ', "-.-"),
+    ('Λ', ".-.."),
+    ('Μ', "--"),
+    ('Ν', "-."),
+    ('Ξ', "-..-"),
+    ('Ο', "---"),
+    ('Π', ".--."),
+    ('Ρ', ".-."),
+    ('Σ', "..."),
+    ('Τ', "-"),
+    ('Υ', "-.--"),
+    ('Φ', "..-."),
+    ('Χ', "----"),
+    ('Ψ', "--.-"),
+    ('Ω', ".--"),
+];
+
+/// Russian Morse. Ё is sent like Е, and the soft and hard signs have codes
+/// of their own rather than being dropped.
+const CYRILLIC: &[(char, &str)] = &[
+    ('А', ".-"),
+    ('Б', "-..."),
+    ('В', ".--"),
+    ('Г', "--."),
+    ('Д', "-.."),
+    ('Е', "."),
+    ('Ё', "."),
+    ('Ж', "...-"),
+    ('З', "--.."),
+    ('И', ".."),
+    ('Й', ".---"),
+    ('К', "-.-"),
+    ('Л', ".-.."),
+    ('М', "--"),
+    ('Н', "-."),
+    ('О', "---"),
+    ('П', ".--."),
+    ('Р', ".-."),
+    ('С', "..."),
+    ('Т', "-"),
+    ('У', "..-"),
+    ('Ф', "..-."),
+    ('Х', "...."),
+    ('Ц', "-.-."),
+    ('Ч', "---."),
+    ('Ш', "----"),
+    ('Щ', "--.-"),
+    ('Ъ', "--.--"),
+    ('Ы', "-.--"),
+    ('Ь', "-..-"),
+    ('Э', "..-.."),
+    ('Ю', "..--"),
+    ('Я', ".-.-"),
+];
+
+/// Hebrew Morse, which follows the codes of the Latin letters the Hebrew
+/// ones correspond to. Final forms are sent like the regular letters, which
+/// are listed first so that decoding yields them.
+const HEBREW: &[(char, &str)] = &[
+    ('א', ".-"),
+    ('ב', "-..."),
+    ('ג', "--."),
+    ('ד', "-.."),
+    ('ה', "---"),
+    ('ו', "."),
+    ('ז', "--.."),
+    ('ח', "...."),
+    ('ט', "..-"),
+    ('י', ".."),
+    ('כ', "-.-"),
+    ('ל', ".-.."),
+    ('מ', "--"),
+    ('נ', "-."),
+    ('ס', "-.-."),
+    ('ע', ".---"),
+    ('פ', ".--."),
+    ('צ', ".--"),
+    ('ק', "--.-"),
+    ('ר', ".-."),
+    ('ש', "..."),
+    ('ת', "-"),
+    ('ך', "-.-"),
+    ('ם', "--"),
+    ('ן', "-."),
+    ('ף', ".--."),
+    ('ץ', ".--"),
+];
+
+const COMMON: &[(char, &str)] = &[
+    ('0', "-----"),
+    ('1', ".----"),
+    ('2', "..---"),
+    ('3', "...--"),
+    ('4', "....-"),
+    ('5', "....."),
+    ('6', "-...."),
+    ('7', "--..."),
+    ('8', "---.."),
+    ('9', "----."),
+    ('.', ".-.-.-"),
+    (',', "--..--"),
+    ('?', "..--.."),
+    ('\'', ".----."),
+    ('!', "-.-.--"),
+    ('/', "-..-."),
+    ('(', "-.--."),
+    (')', "-.--.-"),
+    ('&', ".-..."),
+    (':', "---..."),
+    (';', "-.-.-."),
+    ('=', "-...-"),
+    ('+', ".-.-."),
+    ('-', "-....-"),
+    ('_', "..--.-"),
+    ('"', ".-..-."),
+    ('$', "...-..-"),
+    ('@', ".--.-."),
+];
+
+/// The prosigns that can be sent by name, with their run-together code and
+/// meaning.
+pub const PROSIGNS: &[(&str, &str, &str)] = &[
+    ("AR", ".-.-.", "end of message"),
+    ("AS", ".-...", "wait"),
+    ("BK", "-...-.-", "break"),
+    ("BT", "-...-", "new paragraph"),
+    ("CL", "-.-..-..", "closing down"),
+    ("CT", "-.-.-", "start of transmission"),
+    ("HH", "........", "error, correction follows"),
+    ("KN", "-.--.", "over, only the named station may answer"),
+    ("SK", "...-.-", "end of contact"),
+    ("SN", "...-.", "understood"),
+    ("SOS", "...---...", "distress"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_greek_letters_with_accents() {
+        let words = encode_words("Καλημέρα ΐ ϋ Ώ", Alphabet::Greek);
+        assert_eq!(words[0].len(), 8);
+        assert_eq!(words[0][5].1, ".");
+        assert_eq!(encode_text("ΐ ϋ Ώ", Alphabet::Greek), ".. / -.-- / .--");
+        assert!(unknown_chars("Καλημέρα", Alphabet::Greek).is_empty());
+    }
+
+    #[test]
+    fn encodes_cyrillic_letters_of_either_case() {
+        assert_eq!(
+            encode_text("Мир мир", Alphabet::Cyrillic),
+            "-- .. .-. / -- .. .-."
+        );
+        assert_eq!(
+            encode_text("объём", Alphabet::Cyrillic),
+            "--- -... --.-- . --"
+        );
+        assert_eq!(encode_text("ДЕНЬ", Alphabet::Cyrillic), "-.. . -. -..-");
+    }
+
+    #[test]
+    fn suggests_the_nearest_codes_of_an_unknown_code() {
+        assert_eq!(Alphabet::Latin.decode(".-----."), None);
+        let suggestions = Alphabet::Latin.suggest(".-----.", 3);
+        assert_eq!(suggestions[0], ('\'', ".----."));
+        let distances = suggestions
+            .iter()
+            .map(|(_, code)| edit_distance(".-----.", code))
+            .collect::<Vec<_>>();
+        assert_eq!(distances, [1, 2, 2]);
+    }
+
+    #[test]
+    fn encodes_hebrew_letters_in_the_order_typed() {
+        assert_eq!(encode_text("שלום", Alphabet::Hebrew), "... .-.. . --");
+        // Final forms share the code of their letter.
+        assert_eq!(encode_text("מם כך", Alphabet::Hebrew), "-- -- / -.- -.-");
+        assert_eq!(unknown_chars("שלום", Alphabet::Latin).len(), 4);
+    }
+}
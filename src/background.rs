@@ -0,0 +1,128 @@
+use anyhow::{bail, Result};
+use std::path::Path;
+
+/// A recording mixed underneath the rendered Morse code, such as band noise.
+pub struct Background {
+    /// Interleaved 16-bit samples.
+    samples: Vec<i16>,
+    channels: u16,
+}
+
+impl Background {
+    /// Reads a WAV file at `sample_rate`. With `mono_mixdown`, its channels
+    /// are averaged into one.
+    pub fn read(path: &Path, sample_rate: u32, mono_mixdown: bool) -> Result<Self> {
+        let mut reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
+        if spec.sample_rate != sample_rate {
+            bail!(
+                "{} is sampled at {} Hz, not {} Hz",
+                path.display(),
+                spec.sample_rate,
+                sample_rate
+            );
+        }
+
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 / scale))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+        let to_i16 =
+            |sample: f32| (sample * 32768.0).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+
+        if mono_mixdown {
+            let channels = spec.channels as usize;
+            let samples = samples
+                .chunks(channels)
+                .map(|frame| to_i16(frame.iter().sum::<f32>() / channels as f32))
+                .collect();
+            Ok(Self {
+                samples,
+                channels: 1,
+            })
+        } else {
+            Ok(Self {
+                samples: samples.into_iter().map(to_i16).collect(),
+                channels: spec.channels,
+            })
+        }
+    }
+
+    /// Adds the background to interleaved frames of `channels` channels,
+    /// looping it if it is shorter. A mono background is added to every
+    /// channel, otherwise the channel counts must match.
+    pub fn mix(&self, frames: &mut [i16], channels: u16) -> Result<()> {
+        self.add(frames, channels, true)
+    }
+
+    /// Adds the recording to interleaved frames of `channels` channels
+    /// once. The part of the recording past the end of the frames is cut
+    /// off, and frames past the end of the recording are left as they are.
+    pub fn overlay(&self, frames: &mut [i16], channels: u16) -> Result<()> {
+        self.add(frames, channels, false)
+    }
+
+    fn add(&self, frames: &mut [i16], channels: u16, looped: bool) -> Result<()> {
+        if self.channels != 1 && self.channels != channels {
+            bail!(
+                "the recording has {} channels but the output has {}; consider --mono-mixdown",
+                self.channels,
+                channels
+            );
+        }
+        let background_frames = self.samples.len() / self.channels as usize;
+        if background_frames == 0 {
+            return Ok(());
+        }
+
+        for (index, frame) in frames.chunks_mut(channels as usize).enumerate() {
+            if !looped && index >= background_frames {
+                break;
+            }
+            let start = index % background_frames * self.channels as usize;
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let offset = if self.channels == 1 { 0 } else { channel };
+                *sample = sample.saturating_add(self.samples[start + offset]);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_a_stereo_background_into_mono() {
+        let path = std::env::temp_dir().join(format!("morse-{}-stereo.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for sample in [1000, 3000, -2000, 0] {
+            writer.write_sample(sample as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut frames = [0; 5];
+        let background = Background::read(&path, 8000, true).unwrap();
+        background.mix(&mut frames, 1).unwrap();
+        assert_eq!(frames, [2000, -1000, 2000, -1000, 2000]);
+
+        let stereo = Background::read(&path, 8000, false).unwrap();
+        assert!(stereo.mix(&mut frames, 1).is_err());
+        assert!(Background::read(&path, 44100, true).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
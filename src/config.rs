@@ -0,0 +1,99 @@
+use crate::{envelope::SidetoneShape, waveform::Waveform, OutputFormat};
+use anyhow::{bail, Context, Result};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Defaults for the arguments, loaded from a `[profiles.<name>]` table of
+/// the config file. Flags given on the command line take precedence.
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub frequency: Option<f32>,
+    pub unit: Option<f32>,
+    pub wpm: Option<f32>,
+    pub waveform: Option<Waveform>,
+    pub volume: Option<f32>,
+    pub output_format: Option<OutputFormat>,
+    pub sidetone_shape: Option<SidetoneShape>,
+    pub pre_emphasis: Option<f32>,
+}
+
+impl Profile {
+    /// Takes the settings this profile leaves out from `fallback`.
+    pub fn or(self, fallback: Profile) -> Profile {
+        Profile {
+            frequency: self.frequency.or(fallback.frequency),
+            unit: self.unit.or(fallback.unit),
+            wpm: self.wpm.or(fallback.wpm),
+            waveform: self.waveform.or(fallback.waveform),
+            volume: self.volume.or(fallback.volume),
+            output_format: self.output_format.or(fallback.output_format),
+            sidetone_shape: self.sidetone_shape.or(fallback.sidetone_shape),
+            pre_emphasis: self.pre_emphasis.or(fallback.pre_emphasis),
+        }
+    }
+}
+
+/// The config file in the user's config directory, usually
+/// `~/.config/morse-cli.toml`.
+pub fn default_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config_dir.join("morse-cli.toml"))
+}
+
+/// Loads the profile called `name` from the config file at `path`.
+pub fn load_profile(path: &Path, name: &str) -> Result<Profile> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let config = content
+        .parse::<toml::Table>()
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    let Some(profile) = config
+        .get("profiles")
+        .and_then(|profiles| profiles.get(name))
+        .and_then(toml::Value::as_table)
+    else {
+        bail!("no profile {} in {}", name, path.display());
+    };
+
+    let number = |key: &str| -> Result<Option<f32>> {
+        match profile.get(key) {
+            None => Ok(None),
+            Some(toml::Value::Float(value)) => Ok(Some(*value as f32)),
+            Some(toml::Value::Integer(value)) => Ok(Some(*value as f32)),
+            Some(_) => bail!("{} of profile {} must be a number", key, name),
+        }
+    };
+
+    Ok(Profile {
+        frequency: number("frequency")?,
+        unit: number("unit")?,
+        wpm: number("wpm")?,
+        waveform: parsed(profile, name, "waveform")?,
+        volume: number("volume")?,
+        output_format: parsed(profile, name, "output_format")?,
+        sidetone_shape: parsed(profile, name, "sidetone_shape")?,
+        pre_emphasis: number("pre_emphasis")?,
+    })
+}
+
+/// Parses a string setting of a profile.
+fn parsed<T>(profile: &toml::Table, name: &str, key: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match profile.get(key) {
+        None => Ok(None),
+        Some(toml::Value::String(value)) => match value.parse() {
+            Ok(value) => Ok(Some(value)),
+            Err(error) => bail!("{} of profile {}: {}", key, name, error),
+        },
+        Some(_) => bail!("{} of profile {} must be a string", key, name),
+    }
+}
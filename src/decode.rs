@@ -0,0 +1,968 @@
+use crate::alphabet::{prosign, Alphabet};
+use anyhow::Result;
+use std::{
+    f32::consts::PI,
+    io::{Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+/// The prosigns that end an over.
+const OVER_ENDINGS: &[&str] = &["AR", "KN", "SK"];
+
+/// The level below which a stream is considered silent, however quiet it
+/// has been recently.
+const STREAM_NOISE_FLOOR: f32 = 0.01;
+
+/// How much the reference level of a stream decays per second, so that the
+/// decoder adapts to a signal that gets weaker.
+const STREAM_PEAK_DECAY: f32 = 0.8;
+
+/// The number of recent segments a stream's unit is estimated from.
+const STREAM_HISTORY: usize = 64;
+
+/// The number of segments on either side of a segment that its unit is
+/// estimated from when decoding adaptively.
+const ADAPTIVE_WINDOW: usize = 12;
+
+/// The spacing, in Hz, of the frequencies the tone of a recording is
+/// searched at.
+const SCAN_STEP: f32 = 5.0;
+
+/// The width, in units, of the bins of a duration histogram.
+const HISTOGRAM_BIN: f32 = 0.25;
+
+/// Durations at least this many units long share the last bin of a
+/// duration histogram.
+const HISTOGRAM_MAX: f32 = 10.0;
+
+/// The length of the longest bar of a duration histogram.
+const HISTOGRAM_WIDTH: usize = 50;
+
+/// The boundaries, in units, between the three kinds of gaps.
+#[derive(Debug, Clone, Copy)]
+pub struct GapThresholds {
+    /// Gaps at least this long separate letters rather than elements.
+    pub letter: f32,
+    /// Gaps at least this long separate words rather than letters.
+    pub word: f32,
+}
+
+impl Default for GapThresholds {
+    /// The midpoints between the ITU ratios of 1, 3, and 7 units.
+    fn default() -> Self {
+        Self {
+            letter: 2.0,
+            word: 5.0,
+        }
+    }
+}
+
+impl FromStr for GapThresholds {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (letter, word) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected <letter>:<word>, got {}", s))?;
+        let letter = letter.trim().parse::<f32>().map_err(|e| e.to_string())?;
+        let word = word.trim().parse::<f32>().map_err(|e| e.to_string())?;
+        if !(1.0 < letter && letter < 3.0 && 3.0 < word && word < 7.0) {
+            return Err(format!(
+                "the letter threshold must lie between 1 and 3 units and the word threshold \
+                 between 3 and 7: {}",
+                s
+            ));
+        }
+
+        Ok(Self { letter, word })
+    }
+}
+
+/// Settings for decoding Morse code from audio.
+pub struct DecodeOptions {
+    /// The frequency of the tone to listen for, in Hz.
+    pub frequency: f32,
+    /// The width, in Hz, of the band around the tone that the detector
+    /// listens to. It determines the length of the blocks the recording is
+    /// analyzed in, so narrower bands reject adjacent signals better but
+    /// resolve element edges less precisely.
+    pub bandwidth: f32,
+    /// Whether each block is weighted with a Hann window before its level
+    /// is measured, which reduces the leakage of adjacent signals into the
+    /// tone's band. The window lets slightly more broadband noise through.
+    pub window: bool,
+    /// How far, in Hz, the tone of a recording may be from the frequency.
+    /// The decoder locks onto the strongest tone within it. Streams are
+    /// always listened to at the frequency.
+    pub tolerance: f32,
+    /// Segments of a recording shorter than this many seconds are merged
+    /// into the segment before them, to ignore blips of noise and
+    /// dropouts.
+    pub min_element: f32,
+    /// The fraction of the loudest level above which a block counts as
+    /// key-down.
+    pub threshold: f32,
+    /// Elements at least this many units long are dahs.
+    pub dit_dah: f32,
+    pub gaps: GapThresholds,
+    /// Whether short elements are read as dahs and long ones as dits, for
+    /// recordings from a keyer with swapped paddles.
+    pub swap: bool,
+    /// Whether the unit is estimated from the neighbouring segments of each
+    /// segment rather than from the whole recording, to follow a sender
+    /// whose speed drifts. Streams are always decoded this way.
+    pub adaptive: bool,
+    /// The table codes are looked up in.
+    pub alphabet: Alphabet,
+}
+
+impl DecodeOptions {
+    /// The number of samples per analysis block.
+    fn block_len(&self, sample_rate: u32) -> usize {
+        ((sample_rate as f32 / self.bandwidth) as usize).max(1)
+    }
+}
+
+/// A period of key-down or key-up detected in a recording.
+#[derive(Debug, Clone, Copy)]
+pub struct Segment {
+    pub on: bool,
+    /// When the segment starts, in seconds from the start of the recording.
+    pub start: f32,
+    /// The duration of the segment in seconds.
+    pub duration: f32,
+}
+
+/// A character decoded from a recording. Word breaks are represented by a
+/// space without a code.
+#[derive(Debug, Clone)]
+pub struct Character {
+    /// The decoded character, or `?` if the code isn't in the table.
+    pub value: char,
+    pub code: String,
+    /// How cleanly the durations of the character's elements fell into the
+    /// dit, dah, and space bins, from 0 to 100.
+    pub confidence: f32,
+    /// When the character's first element starts, or the word break's gap,
+    /// in seconds from the start of the recording.
+    pub start: f32,
+    /// The time from the start of the first element to the end of the last
+    /// one, or the length of the gap, in seconds.
+    pub duration: f32,
+}
+
+/// How closely the timing of a recording follows the ideal ratios of 1
+/// unit for dits and the gaps within letters, 3 for dahs and the gaps
+/// between letters, and 7 for the gaps between words.
+#[derive(Debug, Clone)]
+pub struct Grade {
+    /// The estimated length of a unit in seconds, which is the median dit
+    /// as measured by [`median_dit`].
+    pub unit: f32,
+    /// The name of each kind of element or gap that occurs, how often it
+    /// occurs, and the mean deviation of its lengths from the ideal, as a
+    /// fraction of the ideal.
+    pub kinds: Vec<(&'static str, usize, f32)>,
+    /// The "fist quality" from 0 to 100, which is 100 less the mean
+    /// deviation of all elements and gaps in percent.
+    pub quality: f32,
+}
+
+/// How decoded recordings are printed.
+#[derive(Debug, Clone, Copy)]
+pub enum DecodeFormat {
+    Text,
+    /// An array of the characters with their timing.
+    Json,
+}
+
+impl FromStr for DecodeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use DecodeFormat::*;
+
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Text),
+            "json" => Ok(Json),
+            _ => Err(format!("unknown decode format: {}", s)),
+        }
+    }
+}
+
+/// Reads a WAV file as samples between -1 and 1. Recordings with more than
+/// one channel are mixed down to mono by averaging the channels.
+pub fn read_wav(path: &Path) -> Result<(Vec<f32>, u32)> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+    let channels = spec.channels as usize;
+    let samples = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    Ok((samples, spec.sample_rate))
+}
+
+/// Decodes a WAV recording.
+pub fn decode_wav(path: &Path, options: &DecodeOptions) -> Result<Vec<Character>> {
+    let (samples, sample_rate) = read_wav(path)?;
+    Ok(decode(&samples, sample_rate, options))
+}
+
+/// Decodes the segments of a recording, with a histogram of their
+/// durations and the frequency the tone was detected at.
+pub fn decode_wav_plot(
+    path: &Path,
+    options: &DecodeOptions,
+) -> Result<(Vec<Character>, String, f32)> {
+    let (samples, sample_rate) = read_wav(path)?;
+    let frequency = tone_frequency(&samples, sample_rate, options);
+    let segments = segment_at(&samples, sample_rate, frequency, options);
+    Ok((
+        decode_segments(&segments, options),
+        histogram(&segments),
+        frequency,
+    ))
+}
+
+/// The median length, in seconds, of the dits in a recording, or `None`
+/// if it contains no elements. The elements are only segmented, not
+/// decoded. A recording of dahs alone gives the unit estimated from them.
+pub fn median_dit(path: &Path, options: &DecodeOptions) -> Result<Option<f32>> {
+    let (samples, sample_rate) = read_wav(path)?;
+    let segments = segment(&samples, sample_rate, options);
+    Ok(median_dit_of(&segments, options))
+}
+
+/// The median length, in seconds, of the dits among the segments, or the
+/// estimated unit if there are none, or `None` if there are no elements.
+fn median_dit_of(segments: &[Segment], options: &DecodeOptions) -> Option<f32> {
+    let unit = estimate_unit(segments)?;
+    let mut dits = segments
+        .iter()
+        .filter(|segment| segment.on && segment.duration / unit < options.dit_dah)
+        .map(|segment| segment.duration)
+        .collect::<Vec<_>>();
+    dits.sort_by(f32::total_cmp);
+
+    dits.get(dits.len() / 2).copied().or(Some(unit))
+}
+
+/// Grades the timing of a recording, or returns `None` if it contains no
+/// elements. Elements and gaps are classified as for decoding. Gaps longer
+/// than a word gap aren't counted as deviations, as pauses between words
+/// are up to the sender.
+pub fn grade_wav(path: &Path, options: &DecodeOptions) -> Result<Option<Grade>> {
+    let (samples, sample_rate) = read_wav(path)?;
+    let segments = segment(&samples, sample_rate, options);
+    let (Some(unit), Some(dit)) = (
+        unit_of(&segments, options),
+        median_dit_of(&segments, options),
+    ) else {
+        return Ok(None);
+    };
+
+    let GapThresholds { letter, word } = options.gaps;
+    let names = ["dits", "dahs", "symbol gaps", "letter gaps", "word gaps"];
+    let mut deviations = [0.0; 5];
+    let mut counts = [0; 5];
+    for (index, segment) in segments.iter().enumerate() {
+        let units = segment.duration / unit(index);
+        let (kind, ideal) = match (segment.on, units) {
+            (true, units) if units < options.dit_dah => (0, 1.0),
+            (true, _) => (1, 3.0),
+            (false, units) if units < letter => (2, 1.0),
+            (false, units) if units < word => (3, 3.0),
+            (false, _) => (4, 7.0),
+        };
+        let units = if kind == 4 { units.min(ideal) } else { units };
+        deviations[kind] += ((units - ideal).abs() / ideal).min(1.0);
+        counts[kind] += 1;
+    }
+
+    let total = counts.iter().sum::<usize>();
+    let quality = 100.0 * (1.0 - deviations.iter().sum::<f32>() / total as f32);
+    let kinds = (0..names.len())
+        .filter(|kind| counts[*kind] > 0)
+        .map(|kind| {
+            let count = counts[kind];
+            (names[kind], count, deviations[kind] / count as f32)
+        })
+        .collect();
+
+    Ok(Some(Grade {
+        unit: dit,
+        kinds,
+        quality,
+    }))
+}
+
+/// Decodes a buffer of samples.
+pub fn decode(samples: &[f32], sample_rate: u32, options: &DecodeOptions) -> Vec<Character> {
+    decode_segments(&segment(samples, sample_rate, options), options)
+}
+
+/// Decodes raw little-endian 16-bit mono PCM as it is read, writing the
+/// characters to `output` as soon as they are resolved.
+pub fn decode_pcm(
+    mut input: impl Read,
+    sample_rate: u32,
+    options: &DecodeOptions,
+    mut output: impl Write,
+) -> Result<()> {
+    let mut decoder = StreamDecoder::new(options, sample_rate);
+    let mut buffer = [0; 8192];
+    // A read may end in the middle of a sample.
+    let mut len = 0;
+
+    loop {
+        let read = match input.read(&mut buffer[len..]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error.into()),
+        };
+        len += read;
+
+        let samples = buffer[..len - len % 2]
+            .chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / i16::MAX as f32)
+            .collect::<Vec<_>>();
+        buffer.copy_within(len - len % 2..len, 0);
+        len %= 2;
+
+        output.write_all(decoder.push(&samples).as_bytes())?;
+        output.flush()?;
+    }
+
+    writeln!(output, "{}", decoder.finish())?;
+    Ok(())
+}
+
+/// The decoded text, with `?` for unknown codes.
+pub fn text(characters: &[Character]) -> String {
+    characters.iter().map(|character| character.value).collect()
+}
+
+/// The decoded text, with each over on its own line. The prosigns ending
+/// the overs are written by name in angle brackets, like `<AR>`.
+pub fn split_overs(characters: &[Character]) -> String {
+    let mut text = String::new();
+    for character in characters {
+        let ending = OVER_ENDINGS
+            .iter()
+            .find(|name| prosign(name) == Some(character.code.as_str()));
+        match ending {
+            Some(name) => text.push_str(&format!("<{}>\n", name)),
+            // A word break after an over doesn't start the next one.
+            None if character.value == ' ' && text.ends_with('\n') => {}
+            None => text.push(character.value),
+        }
+    }
+
+    text.trim_end().to_owned()
+}
+
+/// The level of the tone in a block of samples, using the Goertzel
+/// algorithm, optionally with the block weighted by a Hann window.
+fn goertzel(block: &[f32], frequency: f32, sample_rate: u32, window: bool) -> f32 {
+    let coefficient = 2.0 * (2.0 * PI * frequency / sample_rate as f32).cos();
+    let weight = |index: usize| {
+        if window && block.len() > 1 {
+            0.5 - 0.5 * (2.0 * PI * index as f32 / block.len() as f32).cos()
+        } else {
+            1.0
+        }
+    };
+    let (mut s1, mut s2) = (0.0, 0.0);
+    let mut weights = 0.0;
+    for (index, sample) in block.iter().enumerate() {
+        let weight = weight(index);
+        weights += weight;
+        let s0 = sample * weight + coefficient * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+
+    // Dividing by the sum of the weights keeps the level of a steady tone
+    // independent of the window.
+    let power = s1 * s1 + s2 * s2 - coefficient * s1 * s2;
+    2.0 * power.max(0.0).sqrt() / weights
+}
+
+/// The level of the tone at `frequency` in each block of the samples.
+fn levels(samples: &[f32], sample_rate: u32, frequency: f32, options: &DecodeOptions) -> Vec<f32> {
+    let block_len = options.block_len(sample_rate);
+    // A window over a partial block at the end would weight its few samples
+    // as heavily as a whole block, so the block is dropped instead.
+    samples
+        .chunks(block_len)
+        .filter(|block| !options.window || block.len() == block_len)
+        .map(|block| goertzel(block, frequency, sample_rate, options.window))
+        .collect()
+}
+
+/// The frequency within the tolerance at which the levels of all blocks
+/// add up to the most, scanned in steps of [`SCAN_STEP`].
+fn tone_frequency(samples: &[f32], sample_rate: u32, options: &DecodeOptions) -> f32 {
+    let steps = (options.tolerance / SCAN_STEP) as i32;
+    let mut best = (options.frequency, f32::NEG_INFINITY);
+    for step in -steps..=steps {
+        let frequency = options.frequency + step as f32 * SCAN_STEP;
+        if frequency <= 0.0 || frequency >= sample_rate as f32 / 2.0 {
+            continue;
+        }
+        let energy = levels(samples, sample_rate, frequency, options)
+            .iter()
+            .sum::<f32>();
+        if energy > best.1 {
+            best = (frequency, energy);
+        }
+    }
+
+    best.0
+}
+
+/// Splits the recording into alternating key-down and key-up segments.
+/// Silence before the first and after the last element is trimmed.
+pub fn segment(samples: &[f32], sample_rate: u32, options: &DecodeOptions) -> Vec<Segment> {
+    let frequency = tone_frequency(samples, sample_rate, options);
+    segment_at(samples, sample_rate, frequency, options)
+}
+
+/// Segments the recording like [`segment`], listening for the tone at a
+/// frequency that is already known.
+fn segment_at(
+    samples: &[f32],
+    sample_rate: u32,
+    frequency: f32,
+    options: &DecodeOptions,
+) -> Vec<Segment> {
+    let block_len = options.block_len(sample_rate);
+    let block_duration = block_len as f32 / sample_rate as f32;
+    let levels = levels(samples, sample_rate, frequency, options);
+    let threshold = levels.iter().copied().fold(0.0, f32::max) * options.threshold;
+
+    let mut segments: Vec<Segment> = Vec::new();
+    for (index, level) in levels.into_iter().enumerate() {
+        let on = threshold > 0.0 && level > threshold;
+        match segments.last_mut() {
+            Some(segment) if segment.on == on => segment.duration += block_duration,
+            _ => segments.push(Segment {
+                on,
+                start: index as f32 * block_duration,
+                duration: block_duration,
+            }),
+        }
+    }
+    let mut segments = debounce(segments, options.min_element);
+
+    if segments.last().is_some_and(|segment| !segment.on) {
+        segments.pop();
+    }
+    if segments.first().is_some_and(|segment| !segment.on) {
+        segments.remove(0);
+    }
+
+    segments
+}
+
+/// Merges the segments shorter than `min` seconds into the segment before
+/// them, and the segments that then follow one in the same state as well.
+fn debounce(segments: Vec<Segment>, min: f32) -> Vec<Segment> {
+    let mut debounced: Vec<Segment> = Vec::new();
+    for segment in segments {
+        match debounced.last_mut() {
+            Some(last) if segment.duration < min || last.on == segment.on => {
+                last.duration += segment.duration;
+            }
+            _ => debounced.push(segment),
+        }
+    }
+
+    debounced
+}
+
+/// The centre of the shorter of two duration clusters, or `None` if all
+/// durations are alike.
+fn shorter_cluster(durations: &[f32]) -> Option<f32> {
+    let min = durations.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = durations.iter().copied().fold(0.0, f32::max);
+    if max < 2.0 * min {
+        return None;
+    }
+
+    let mean = |durations: Vec<f32>| durations.iter().sum::<f32>() / durations.len() as f32;
+    let (mut short, mut long) = (min, max);
+    for _ in 0..16 {
+        let split = (short + long) / 2.0;
+        short = mean(durations.iter().copied().filter(|d| *d <= split).collect());
+        long = mean(durations.iter().copied().filter(|d| *d > split).collect());
+    }
+
+    Some(short)
+}
+
+/// Estimates the length of a unit from the key-down segments, falling back
+/// to the shortest key-up segment if all elements have the same length.
+fn estimate_unit(segments: &[Segment]) -> Option<f32> {
+    let on = segments
+        .iter()
+        .filter(|segment| segment.on)
+        .map(|segment| segment.duration)
+        .collect::<Vec<_>>();
+    if on.is_empty() {
+        return None;
+    }
+
+    shorter_cluster(&on).or_else(|| {
+        let element = on.iter().sum::<f32>() / on.len() as f32;
+        let gap = segments
+            .iter()
+            .filter(|segment| !segment.on)
+            .map(|segment| segment.duration)
+            .fold(f32::INFINITY, f32::min);
+
+        // Elements much longer than the shortest gap are dahs.
+        if element > 2.0 * gap {
+            Some(element / 3.0)
+        } else {
+            Some(element)
+        }
+    })
+}
+
+/// How close `units` is to the `ideal` length of its bin, from 0 to 1.
+/// `below` and `above` are the distances from the ideal to the bin's
+/// bounds.
+fn bin_score(units: f32, ideal: f32, below: f32, above: f32) -> f32 {
+    let distance = if units < ideal {
+        (ideal - units) / below
+    } else {
+        (units - ideal) / above
+    };
+
+    (1.0 - distance).clamp(0.0, 1.0)
+}
+
+/// Completes the character whose code has been collected so far. `span` is
+/// the start of its first element and the end of its last one.
+fn finish(
+    characters: &mut Vec<Character>,
+    alphabet: Alphabet,
+    code: &mut String,
+    scores: &mut Vec<f32>,
+    span: &mut Option<(f32, f32)>,
+) {
+    let Some((start, end)) = span.take() else {
+        return;
+    };
+
+    characters.push(Character {
+        value: alphabet.decode(code).unwrap_or('?'),
+        code: std::mem::take(code),
+        confidence: 100.0 * scores.iter().sum::<f32>() / scores.len() as f32,
+        start,
+        duration: end - start,
+    });
+    scores.clear();
+}
+
+/// Histograms of the durations of the key-down and key-up segments, in
+/// units, to show the clusters of dits and dahs and of the three kinds of
+/// gaps.
+pub fn histogram(segments: &[Segment]) -> String {
+    let Some(unit) = estimate_unit(segments) else {
+        return String::from("no elements detected\n");
+    };
+
+    let last = (HISTOGRAM_MAX / HISTOGRAM_BIN) as usize;
+    let mut text = String::new();
+    for (on, name) in [(true, "key-down"), (false, "key-up")] {
+        let mut counts = vec![0; last + 1];
+        for segment in segments.iter().filter(|segment| segment.on == on) {
+            let bin = (segment.duration / unit / HISTOGRAM_BIN) as usize;
+            counts[bin.min(last)] += 1;
+        }
+        let Some(first) = counts.iter().position(|count| *count > 0) else {
+            continue;
+        };
+        let end = counts.iter().rposition(|count| *count > 0).unwrap() + 1;
+        let max = counts.iter().copied().max().unwrap();
+
+        text.push_str(&format!("{}, in units of {:.1} ms:\n", name, unit * 1000.0));
+        for (bin, count) in counts.iter().enumerate().take(end).skip(first) {
+            let label = if bin == last {
+                format!(">={}", HISTOGRAM_MAX)
+            } else {
+                format!("{:.2}", bin as f32 * HISTOGRAM_BIN)
+            };
+            let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(max));
+            let row = format!("{:>5} {:>4} {}", label, count, bar);
+            text.push_str(row.trim_end());
+            text.push('\n');
+        }
+    }
+
+    text
+}
+
+/// Classifies the segments into elements and gaps and looks up the
+/// resulting codes.
+pub fn decode_segments(segments: &[Segment], options: &DecodeOptions) -> Vec<Character> {
+    let Some(unit) = unit_of(segments, options) else {
+        return Vec::new();
+    };
+    classify(segments, unit, options)
+}
+
+/// The unit of each segment, given by its index, or `None` if there are no
+/// elements. Without `adaptive`, all segments share the unit of the whole
+/// recording. Otherwise the unit is estimated from the segments around the
+/// segment, or only from those before or after it if the segment fits
+/// their unit better, so that a segment next to a change of speed is
+/// measured at its own speed.
+fn unit_of<'a>(
+    segments: &'a [Segment],
+    options: &DecodeOptions,
+) -> Option<impl Fn(usize) -> f32 + 'a> {
+    let unit = estimate_unit(segments)?;
+    let adaptive = options.adaptive;
+
+    Some(move |index: usize| {
+        if !adaptive {
+            return unit;
+        }
+        let start = index.saturating_sub(ADAPTIVE_WINDOW);
+        let end = (index + ADAPTIVE_WINDOW + 1).min(segments.len());
+        let estimate = |segments: &[Segment]| estimate_unit(segments).unwrap_or(unit);
+        let misfit = |unit: f32| misfit(&segments[index], unit);
+
+        // Only whole sides of the window are reliable on their own.
+        let sides = [&segments[start..=index], &segments[index..end]];
+        sides
+            .into_iter()
+            .filter(|side| side.len() > ADAPTIVE_WINDOW)
+            .map(estimate)
+            .fold(estimate(&segments[start..end]), |best, unit| {
+                if misfit(unit) < misfit(best) {
+                    unit
+                } else {
+                    best
+                }
+            })
+    })
+}
+
+/// How far the length of a segment is from the nearest ideal length of its
+/// kind at the unit, as a ratio. Gaps longer than a word gap fit any unit.
+fn misfit(segment: &Segment, unit: f32) -> f32 {
+    let units = segment.duration / unit;
+    let ideals: &[f32] = if segment.on {
+        &[1.0, 3.0]
+    } else if units >= 7.0 {
+        return 0.0;
+    } else {
+        &[1.0, 3.0, 7.0]
+    };
+
+    ideals
+        .iter()
+        .map(|ideal| (units / ideal).ln().abs())
+        .fold(f32::INFINITY, f32::min)
+}
+
+/// Classifies the segments, with the unit of each segment given by its
+/// index.
+fn classify(
+    segments: &[Segment],
+    unit: impl Fn(usize) -> f32,
+    options: &DecodeOptions,
+) -> Vec<Character> {
+    let mut characters = Vec::new();
+    let mut code = String::new();
+    let mut scores = Vec::new();
+    let mut span = None;
+
+    let GapThresholds { letter, word } = options.gaps;
+    let (short, long) = if options.swap { ('-', '.') } else { ('.', '-') };
+    for (index, segment) in segments.iter().enumerate() {
+        let units = segment.duration / unit(index);
+
+        if segment.on {
+            let end = segment.start + segment.duration;
+            span = Some((span.map_or(segment.start, |(start, _)| start), end));
+            if units < options.dit_dah {
+                code.push(short);
+                scores.push(bin_score(units, 1.0, 1.0, options.dit_dah - 1.0));
+            } else {
+                code.push(long);
+                scores.push(bin_score(units, 3.0, 3.0 - options.dit_dah, 1.0));
+            }
+        } else if units < letter {
+            scores.push(bin_score(units, 1.0, 1.0, letter - 1.0));
+        } else if units < word {
+            finish(
+                &mut characters,
+                options.alphabet,
+                &mut code,
+                &mut scores,
+                &mut span,
+            );
+        } else {
+            finish(
+                &mut characters,
+                options.alphabet,
+                &mut code,
+                &mut scores,
+                &mut span,
+            );
+            // However long a silence is, it only separates two words.
+            if characters
+                .last()
+                .is_none_or(|character| character.value == ' ')
+            {
+                continue;
+            }
+            characters.push(Character {
+                value: ' ',
+                code: String::new(),
+                confidence: 100.0 * bin_score(units, 7.0, 7.0 - word, f32::INFINITY),
+                start: segment.start,
+                duration: segment.duration,
+            });
+        }
+    }
+    finish(
+        &mut characters,
+        options.alphabet,
+        &mut code,
+        &mut scores,
+        &mut span,
+    );
+
+    characters
+}
+
+/// Decodes a stream of samples incrementally. Characters are resolved as
+/// soon as the gap after them is long enough, and the unit is estimated
+/// from the recent segments only.
+pub struct StreamDecoder<'a> {
+    options: &'a DecodeOptions,
+    sample_rate: u32,
+    block: Vec<f32>,
+    /// The loudest recent level, decaying over time.
+    peak: f32,
+    /// The segment that is still going on.
+    current: Segment,
+    history: Vec<Segment>,
+    /// The segments of the character that hasn't been resolved yet. They are
+    /// only classified once it is, so that the unit has been estimated from
+    /// as many segments as possible.
+    pending: Vec<Segment>,
+    /// Whether the current gap has already been resolved as a word break.
+    word_break: bool,
+    /// The time, in seconds, of the blocks processed so far.
+    time: f32,
+}
+
+impl<'a> StreamDecoder<'a> {
+    pub fn new(options: &'a DecodeOptions, sample_rate: u32) -> Self {
+        Self {
+            options,
+            sample_rate,
+            block: Vec::with_capacity(options.block_len(sample_rate)),
+            peak: 0.0,
+            current: Segment {
+                on: false,
+                start: 0.0,
+                duration: 0.0,
+            },
+            history: Vec::new(),
+            pending: Vec::new(),
+            // Leading silence doesn't separate words.
+            word_break: true,
+            time: 0.0,
+        }
+    }
+
+    /// Feeds samples to the decoder and returns the characters resolved by
+    /// them.
+    pub fn push(&mut self, samples: &[f32]) -> String {
+        let block_len = self.options.block_len(self.sample_rate);
+        let mut resolved = String::new();
+
+        for sample in samples {
+            self.block.push(*sample);
+            if self.block.len() == block_len {
+                let level = goertzel(
+                    &self.block,
+                    self.options.frequency,
+                    self.sample_rate,
+                    self.options.window,
+                );
+                self.block.clear();
+                self.process_block(level, &mut resolved);
+            }
+        }
+
+        resolved
+    }
+
+    /// Resolves the character that is still pending at the end of the
+    /// stream.
+    pub fn finish(&mut self) -> String {
+        if self.current.on {
+            self.finish_segment();
+        }
+        self.current = Segment {
+            on: false,
+            start: self.time,
+            duration: 0.0,
+        };
+
+        let mut resolved = String::new();
+        if let Some(unit) = estimate_unit(&self.history) {
+            self.resolve(unit, &mut resolved);
+        }
+
+        resolved
+    }
+
+    fn process_block(&mut self, level: f32, resolved: &mut String) {
+        let block_duration =
+            self.options.block_len(self.sample_rate) as f32 / self.sample_rate as f32;
+        self.peak = level.max(self.peak * STREAM_PEAK_DECAY.powf(block_duration));
+        let on = level > STREAM_NOISE_FLOOR && level > self.peak * self.options.threshold;
+
+        if on != self.current.on {
+            self.finish_segment();
+            self.current = Segment {
+                on,
+                start: self.time,
+                duration: 0.0,
+            };
+        }
+        self.current.duration += block_duration;
+        self.time += block_duration;
+
+        // Resolve the gap while it is still going on.
+        if !self.current.on {
+            let Some(unit) = estimate_unit(&self.history) else {
+                return;
+            };
+            let units = self.current.duration / unit;
+
+            if units >= self.options.gaps.letter {
+                self.resolve(unit, resolved);
+            }
+            if units >= self.options.gaps.word && !self.word_break {
+                self.word_break = true;
+                resolved.push(' ');
+            }
+        }
+    }
+
+    /// Classifies the pending segments, which may turn out to be more than
+    /// one character.
+    fn resolve(&mut self, unit: f32, resolved: &mut String) {
+        let characters = classify(&self.pending, |_| unit, self.options);
+        resolved.extend(characters.iter().map(|character| character.value));
+        self.pending.clear();
+    }
+
+    /// Records the segment that just ended.
+    fn finish_segment(&mut self) {
+        if self.current.duration == 0.0 {
+            return;
+        }
+
+        if self.history.len() == STREAM_HISTORY {
+            self.history.remove(0);
+        }
+        self.history.push(self.current);
+
+        // Characters start with an element.
+        if self.current.on || !self.pending.is_empty() {
+            self.pending.push(self.current);
+        }
+        if self.current.on {
+            self.word_break = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    fn options(window: bool) -> DecodeOptions {
+        DecodeOptions {
+            frequency: 700.0,
+            bandwidth: 200.0,
+            window,
+            tolerance: 0.0,
+            min_element: 0.0,
+            threshold: 0.5,
+            dit_dah: 2.0,
+            gaps: GapThresholds::default(),
+            swap: false,
+            adaptive: false,
+            alphabet: Alphabet::Latin,
+        }
+    }
+
+    #[test]
+    fn window_widens_the_margin_against_a_nearby_carrier() {
+        // Half a second of a 700 Hz tone and half a second without it, under
+        // a steady carrier at 1175 Hz and light noise.
+        let mut rng = StdRng::seed_from_u64(1);
+        let samples = (0..SAMPLE_RATE)
+            .map(|index| {
+                let time = index as f32 / SAMPLE_RATE as f32;
+                let tone = if time < 0.5 {
+                    0.5 * (2.0 * PI * 700.0 * time).sin()
+                } else {
+                    0.0
+                };
+                tone + 0.4 * (2.0 * PI * 1175.0 * time).sin() + rng.gen_range(-0.02..=0.02)
+            })
+            .collect::<Vec<_>>();
+        // The ratio of the weakest key-down level to the strongest key-up
+        // level, leaving out the blocks around the edge.
+        let margin = |window: bool| {
+            let levels = levels(&samples, SAMPLE_RATE, 700.0, &options(window));
+            let half = levels.len() / 2;
+            let on = levels[..half - 1]
+                .iter()
+                .copied()
+                .fold(f32::INFINITY, f32::min);
+            let off = levels[half + 1..].iter().copied().fold(0.0, f32::max);
+            on / off
+        };
+
+        assert!(margin(false) > 1.0);
+        assert!(
+            margin(true) > 2.0 * margin(false),
+            "{} {}",
+            margin(true),
+            margin(false)
+        );
+    }
+}
@@ -0,0 +1,339 @@
+/// A feedback comb filter.
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    feedback: f32,
+}
+
+impl Comb {
+    fn new(delay: usize, feedback: f32) -> Self {
+        Self {
+            buffer: vec![0.0; delay.max(1)],
+            index: 0,
+            feedback,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.buffer[self.index];
+        self.buffer[self.index] = input + output * self.feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        output
+    }
+}
+
+/// An allpass filter, which diffuses the echoes of the combs.
+struct Allpass {
+    buffer: Vec<f32>,
+    index: usize,
+    gain: f32,
+}
+
+impl Allpass {
+    fn new(delay: usize, gain: f32) -> Self {
+        Self {
+            buffer: vec![0.0; delay.max(1)],
+            index: 0,
+            gain,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.index];
+        let output = delayed - self.gain * input;
+        self.buffer[self.index] = input + self.gain * output;
+        self.index = (self.index + 1) % self.buffer.len();
+        output
+    }
+}
+
+/// Applies a Schroeder reverb of four parallel combs followed by two
+/// allpass filters. `amount` between 0 and 1 is the level of the
+/// reverberated signal mixed into the dry one. The output is extended by
+/// the reverb's tail.
+pub fn reverb(samples: &[i16], amount: f32, sample_rate: u32) -> Vec<i16> {
+    let delay = |milliseconds: f32| (sample_rate as f32 * milliseconds / 1000.0) as usize;
+    let mut combs = [29.7, 37.1, 41.1, 43.7].map(|ms| Comb::new(delay(ms), 0.84));
+    let mut allpasses = [5.0, 1.7].map(|ms| Allpass::new(delay(ms), 0.7));
+
+    let tail = sample_rate as usize * 2;
+    let mut output = samples
+        .iter()
+        .copied()
+        .chain(std::iter::repeat_n(0, tail))
+        .map(|sample| {
+            let dry = sample as f32;
+            let mut wet = combs.iter_mut().map(|comb| comb.process(dry)).sum::<f32>() / 4.0;
+            for allpass in &mut allpasses {
+                wet = allpass.process(wet);
+            }
+
+            (dry * (1.0 - amount) + wet * amount).clamp(i16::MIN as f32, i16::MAX as f32) as i16
+        })
+        .collect::<Vec<_>>();
+
+    // Drop the part of the tail that has decayed to silence.
+    let end = output
+        .iter()
+        .rposition(|sample| *sample != 0)
+        .map_or(0, |index| index + 1)
+        .max(samples.len());
+    output.truncate(end);
+
+    output
+}
+
+/// Applies a first-order high shelf above 1 kHz. The shelf's gain is
+/// `db_per_octave` for every octave between the corner and the Nyquist
+/// frequency, so positive values brighten the harmonics of square tones and
+/// negative ones dull them.
+pub fn pre_emphasis(samples: &[i16], db_per_octave: f32, sample_rate: u32) -> Vec<i16> {
+    let corner = 1000.0_f32.min(sample_rate as f32 / 4.0);
+    let octaves = (sample_rate as f32 / 2.0 / corner).log2();
+    let gain = 10f32.powf(db_per_octave * octaves / 20.0);
+
+    // Split each sample into the output of a one-pole lowpass and the rest,
+    // and scale the rest by the shelf's gain.
+    let alpha = 1.0 - (-2.0 * std::f32::consts::PI * corner / sample_rate as f32).exp();
+    let mut low = 0.0;
+    samples
+        .iter()
+        .map(|&sample| {
+            let sample = sample as f32;
+            low += alpha * (sample - low);
+            let high = sample - low;
+
+            (low + high * gain).clamp(i16::MIN as f32, i16::MAX as f32) as i16
+        })
+        .collect()
+}
+
+/// Ramps the level of the first `fade_in` samples up from silence and of
+/// the last `fade_out` samples down to silence. Fades longer than the
+/// samples are cut short.
+pub fn fade(samples: &mut [i16], fade_in: usize, fade_out: usize) {
+    let len = samples.len();
+    for (index, sample) in samples.iter_mut().enumerate() {
+        let mut gain = 1.0;
+        if index < fade_in {
+            gain = index as f32 / fade_in as f32;
+        }
+        if len - index <= fade_out {
+            gain = f32::min(gain, (len - index - 1) as f32 / fade_out as f32);
+        }
+        *sample = (*sample as f32 * gain) as i16;
+    }
+}
+
+/// Scales the samples so that their integrated loudness, measured as in
+/// EBU R 128, is `target` LUFS. Samples that would clip are clamped.
+#[cfg(feature = "loudness")]
+pub fn normalize_loudness(
+    samples: &[i16],
+    target: f32,
+    sample_rate: u32,
+) -> anyhow::Result<Vec<i16>> {
+    let mut meter = ebur128::EbuR128::new(1, sample_rate, ebur128::Mode::I)?;
+    meter.add_frames_i16(samples)?;
+    let loudness = meter.loudness_global()?;
+    if !loudness.is_finite() {
+        // Silence can't be made louder.
+        return Ok(samples.to_vec());
+    }
+
+    let gain = 10f64.powf((target as f64 - loudness) / 20.0);
+    Ok(samples
+        .iter()
+        .map(|sample| (*sample as f64 * gain).clamp(i16::MIN as f64, i16::MAX as f64) as i16)
+        .collect())
+}
+
+#[cfg(not(feature = "loudness"))]
+pub fn normalize_loudness(
+    _samples: &[i16],
+    _target: f32,
+    _sample_rate: u32,
+) -> anyhow::Result<Vec<i16>> {
+    anyhow::bail!("loudness normalization requires the loudness feature")
+}
+
+/// Resamples interleaved frames of `channels` channels from `from` to `to`
+/// Hz. The output is as long as the input, rounded up to whole frames.
+#[cfg(feature = "resample")]
+pub fn resample(frames: &[i16], channels: usize, from: u32, to: u32) -> anyhow::Result<Vec<i16>> {
+    use rubato::{FftFixedIn, Resampler};
+
+    let len = frames.len() / channels;
+    let input = (0..channels)
+        .map(|channel| {
+            frames
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .map(|sample| *sample as f32 / 32768.0)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut resampler = FftFixedIn::<f32>::new(from as usize, to as usize, 1024, 2, channels)?;
+    let delay = resampler.output_delay();
+    let expected = (len as u64 * to as u64).div_ceil(from as u64) as usize;
+    let mut output = vec![Vec::new(); channels];
+    let mut position = 0;
+    // The resampler is flushed with silence until the delayed output has
+    // caught up with the end of the input.
+    while output[0].len() < delay + expected {
+        let end = (position + resampler.input_frames_next()).min(len);
+        let chunk = input
+            .iter()
+            .map(|channel| &channel[position..end])
+            .collect::<Vec<_>>();
+        let resampled = resampler.process_partial((end > position).then_some(&chunk[..]), None)?;
+        position = end;
+        for (channel, samples) in output.iter_mut().zip(resampled) {
+            channel.extend(samples);
+        }
+    }
+
+    Ok((delay..delay + expected)
+        .flat_map(|index| output.iter().map(move |channel| channel[index]))
+        .map(|sample| (sample * 32768.0).clamp(i16::MIN as f32, i16::MAX as f32) as i16)
+        .collect())
+}
+
+#[cfg(not(feature = "resample"))]
+pub fn resample(
+    _frames: &[i16],
+    _channels: usize,
+    _from: u32,
+    _to: u32,
+) -> anyhow::Result<Vec<i16>> {
+    anyhow::bail!("resampling the output requires the resample feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    /// A sine of `frequency` at half scale, lasting `seconds`, followed by
+    /// as long a silence.
+    fn tone_burst(frequency: f32, seconds: f32) -> Vec<i16> {
+        let len = (SAMPLE_RATE as f32 * seconds) as usize;
+        (0..2 * len)
+            .map(|index| {
+                if index >= len {
+                    return 0;
+                }
+                let time = index as f32 / SAMPLE_RATE as f32;
+                (0.5 * i16::MAX as f32 * (2.0 * PI * frequency * time).sin()) as i16
+            })
+            .collect()
+    }
+
+    /// The sum of the squares of the samples.
+    fn energy(samples: &[i16]) -> f64 {
+        samples.iter().map(|sample| (*sample as f64).powi(2)).sum()
+    }
+
+    #[test]
+    fn reverb_adds_energy_after_the_tone_ends() {
+        let dry = tone_burst(700.0, 0.1);
+        let wet = reverb(&dry, 0.5, SAMPLE_RATE);
+        let end = dry.len() / 2;
+
+        assert!(wet.len() >= dry.len());
+        assert_eq!(energy(&dry[end..]), 0.0);
+        assert!(energy(&wet[end..]) > 0.01 * energy(&dry[..end]));
+    }
+
+    #[test]
+    fn pre_emphasis_raises_highs_relative_to_lows() {
+        let (low, high) = (tone_burst(300.0, 0.1), tone_burst(8000.0, 0.1));
+        // The gain of the filter for the high tone relative to the low one.
+        let tilt = |db_per_octave: f32| {
+            let gain = |samples: &[i16]| {
+                energy(&pre_emphasis(samples, db_per_octave, SAMPLE_RATE)) / energy(samples)
+            };
+            gain(&high) / gain(&low)
+        };
+
+        assert!(tilt(3.0) > 2.0);
+        assert!(tilt(-3.0) < 0.5);
+        assert!((tilt(0.0) - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fades_in_from_and_out_to_silence() {
+        let mut samples = vec![10_000; 1000];
+        fade(&mut samples, 100, 200);
+        assert_eq!((samples[0], samples[999]), (0, 0));
+        assert_eq!(samples[50], 5000);
+        assert_eq!(samples[500], 10_000);
+        assert!(samples[800..].windows(2).all(|pair| pair[0] >= pair[1]));
+
+        // Fades longer than the samples are cut short.
+        let mut short = vec![10_000; 10];
+        fade(&mut short, 100, 100);
+        assert!(short.iter().all(|sample| *sample < 1000));
+    }
+
+    #[cfg(feature = "loudness")]
+    #[test]
+    fn normalizes_sparse_and_dense_tones_to_the_same_loudness() {
+        let loudness = |samples: &[i16]| {
+            let mut meter = ebur128::EbuR128::new(1, SAMPLE_RATE, ebur128::Mode::I).unwrap();
+            meter.add_frames_i16(samples).unwrap();
+            meter.loudness_global().unwrap()
+        };
+        let dense = tone_burst(700.0, 2.0)[..2 * SAMPLE_RATE as usize].to_vec();
+        let sparse = tone_burst(700.0, 0.1).repeat(10);
+
+        let dense = normalize_loudness(&dense, -23.0, SAMPLE_RATE).unwrap();
+        let sparse = normalize_loudness(&sparse, -23.0, SAMPLE_RATE).unwrap();
+        assert!((loudness(&dense) + 23.0).abs() < 0.1);
+        assert!((loudness(&sparse) + 23.0).abs() < 0.1);
+        // The sparse tone is louder while it sounds, to make up for its gaps.
+        let peak = |samples: &[i16]| samples.iter().map(|sample| sample.unsigned_abs()).max();
+        assert!(peak(&sparse) > peak(&dense));
+    }
+
+    #[cfg(not(feature = "loudness"))]
+    #[test]
+    fn loudness_normalization_requires_the_feature() {
+        assert!(normalize_loudness(&[0; 10], -23.0, SAMPLE_RATE).is_err());
+    }
+
+    #[cfg(feature = "resample")]
+    #[test]
+    fn resampling_keeps_the_duration_and_frequency() {
+        let tone = (0..48_000)
+            .map(|index| (16384.0 * (2.0 * PI * 1000.0 * index as f32 / 48_000.0).sin()) as i16)
+            .collect::<Vec<_>>();
+        let resampled = resample(&tone, 1, 48_000, 8000).unwrap();
+        assert_eq!(resampled.len(), 8000);
+
+        // The frequency from the zero crossings of the steady middle part.
+        let middle = &resampled[2000..6000];
+        let crossings = middle
+            .windows(2)
+            .filter(|pair| (pair[0] < 0) != (pair[1] < 0))
+            .count();
+        let frequency = crossings as f32 / 2.0 * 8000.0 / middle.len() as f32;
+        assert!((frequency - 1000.0).abs() < 10.0, "{}", frequency);
+
+        let stereo = tone
+            .iter()
+            .flat_map(|sample| [*sample, 0])
+            .collect::<Vec<_>>();
+        assert_eq!(resample(&stereo, 2, 48_000, 8000).unwrap().len(), 2 * 8000);
+    }
+
+    #[cfg(not(feature = "resample"))]
+    #[test]
+    fn resampling_requires_the_feature() {
+        assert!(resample(&[0; 10], 1, 48_000, 8000).is_err());
+    }
+}
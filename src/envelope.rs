@@ -0,0 +1,205 @@
+use anyhow::{bail, Result};
+use std::{f32::consts::PI, path::Path, str::FromStr};
+
+/// The curve of an envelope's edges.
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    Linear,
+    /// Half a cosine period, which has fewer harmonics than a straight ramp.
+    RaisedCosine,
+}
+
+impl Shape {
+    /// The gain at `x` between 0 (silent) and 1 (full level).
+    fn gain(self, x: f32) -> f32 {
+        match self {
+            Shape::Linear => x,
+            Shape::RaisedCosine => (1.0 - (PI * x).cos()) / 2.0,
+        }
+    }
+}
+
+/// The rise and fall applied to each tone.
+#[derive(Debug, Clone, Copy)]
+pub struct Envelope {
+    /// The rise time in seconds.
+    pub attack: f32,
+    /// The fall time in seconds.
+    pub release: f32,
+    pub shape: Shape,
+}
+
+impl Default for Envelope {
+    /// A rectangular envelope.
+    fn default() -> Self {
+        Self {
+            attack: 0.0,
+            release: 0.0,
+            shape: Shape::Linear,
+        }
+    }
+}
+
+impl Envelope {
+    /// The gain of sample `index` of a tone that is `len` samples long.
+    /// Edges that don't fit into the tone are shortened proportionally.
+    pub fn gain(&self, index: u64, len: u64, sample_rate: u32) -> f32 {
+        let mut attack = self.attack * sample_rate as f32;
+        let mut release = self.release * sample_rate as f32;
+        if attack + release > len as f32 {
+            let scale = len as f32 / (attack + release);
+            attack *= scale;
+            release *= scale;
+        }
+
+        let position = index as f32;
+        let remaining = (len - index) as f32;
+        let mut gain = 1.0;
+        if position < attack {
+            gain = self.shape.gain(position / attack);
+        }
+        if remaining < release {
+            gain = f32::min(gain, self.shape.gain(remaining / release));
+        }
+
+        gain
+    }
+}
+
+/// Envelope presets that sound like common sidetones.
+#[derive(Debug, Clone, Copy)]
+pub enum SidetoneShape {
+    /// Nearly rectangular, only rounded enough to avoid clicks.
+    Hard,
+    /// Slow, smooth edges.
+    Soft,
+    /// The keying filter of a typical transceiver.
+    Rig,
+}
+
+impl FromStr for SidetoneShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use SidetoneShape::*;
+
+        match s.to_lowercase().as_str() {
+            "hard" => Ok(Hard),
+            "soft" => Ok(Soft),
+            "rig" => Ok(Rig),
+            _ => Err(format!("unknown sidetone shape: {}", s)),
+        }
+    }
+}
+
+impl SidetoneShape {
+    pub fn envelope(self) -> Envelope {
+        let (attack, release, shape) = match self {
+            SidetoneShape::Hard => (0.001, 0.001, Shape::Linear),
+            SidetoneShape::Soft => (0.010, 0.010, Shape::RaisedCosine),
+            SidetoneShape::Rig => (0.005, 0.004, Shape::RaisedCosine),
+        };
+
+        Envelope {
+            attack,
+            release,
+            shape,
+        }
+    }
+}
+
+/// Gains that emphasize elements by their position within a letter,
+/// written as a comma-separated list such as `1.3,1`. Elements past the
+/// end of the list are sent at full level.
+#[derive(Debug, Clone)]
+pub struct Accent(Vec<f32>);
+
+impl FromStr for Accent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let gains = s
+            .split(',')
+            .map(|gain| gain.trim().parse::<f32>().map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !gains.iter().all(|gain| *gain >= 0.0 && gain.is_finite()) {
+            return Err(format!("invalid accent: {}", s));
+        }
+
+        Ok(Self(gains))
+    }
+}
+
+impl Accent {
+    /// The gain of the element at `position`, counting from 0.
+    pub fn gain(&self, position: usize) -> f32 {
+        self.0.get(position).copied().unwrap_or(1.0)
+    }
+}
+
+/// An arbitrary envelope, given as gains between 0 and 1 at evenly spaced
+/// points from the start to the end of a tone.
+#[derive(Debug, Clone)]
+pub struct Curve(Vec<f32>);
+
+impl Curve {
+    /// Reads the gains from a file, separated by whitespace or commas. Lines
+    /// starting with `#` are ignored.
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mut gains = Vec::new();
+        for line in content
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+        {
+            for value in line.split(|c: char| c == ',' || c.is_whitespace()) {
+                if value.is_empty() {
+                    continue;
+                }
+                match value.parse::<f32>() {
+                    Ok(gain) if (0.0..=1.0).contains(&gain) => gains.push(gain),
+                    _ => bail!("{}: invalid gain {}", path.display(), value),
+                }
+            }
+        }
+        if gains.is_empty() {
+            bail!("{} contains no gains", path.display());
+        }
+
+        Ok(Self(gains))
+    }
+
+    /// The gain of sample `index` of a tone that is `len` samples long,
+    /// interpolated linearly between the points.
+    pub fn gain(&self, index: u64, len: u64) -> f32 {
+        let last = self.0.len() - 1;
+        if last == 0 || len < 2 {
+            return self.0[0];
+        }
+
+        let position = index as f32 / (len - 1) as f32 * last as f32;
+        let before = (position as usize).min(last - 1);
+        let fraction = position - before as f32;
+        self.0[before] * (1.0 - fraction) + self.0[before + 1] * fraction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_a_curve_between_its_points() {
+        let path = std::env::temp_dir().join(format!("morse-{}-curve.txt", std::process::id()));
+        std::fs::write(&path, "# a ramp\n0, 1\n").unwrap();
+        let curve = Curve::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(curve.gain(0, 101), 0.0);
+        assert!((curve.gain(25, 101) - 0.25).abs() < 1e-6);
+        assert!((curve.gain(50, 101) - 0.5).abs() < 1e-6);
+        assert_eq!(curve.gain(100, 101), 1.0);
+        // A tone of a single sample takes the first point.
+        assert_eq!(curve.gain(0, 1), 0.0);
+    }
+}
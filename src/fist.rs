@@ -0,0 +1,128 @@
+use rand::{rngs::StdRng, Rng};
+use std::{f32::consts::PI, str::FromStr};
+
+/// How skilled the simulated operator of a straight key is.
+#[derive(Debug, Clone, Copy)]
+pub enum Operator {
+    Beginner,
+    Intermediate,
+    Expert,
+}
+
+impl FromStr for Operator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Operator::*;
+
+        match s.to_lowercase().as_str() {
+            "beginner" => Ok(Beginner),
+            "intermediate" => Ok(Intermediate),
+            "expert" => Ok(Expert),
+            _ => Err(format!("unknown operator: {}", s)),
+        }
+    }
+}
+
+/// The timing errors of an operator.
+#[derive(Debug, Clone, Copy)]
+struct TimingErrors {
+    /// The standard deviation of an element's or space's length, relative
+    /// to its ideal length.
+    spread: f32,
+    /// How much of the previous error carries over into the next one.
+    /// Operators tend to drift rather than err independently.
+    correlation: f32,
+    /// The chance that a dah is held for too long.
+    long_dah_chance: f64,
+    /// How much longer such a dah is.
+    long_dah_factor: f32,
+}
+
+impl Operator {
+    fn timing_errors(self) -> TimingErrors {
+        let (spread, correlation, long_dah_chance, long_dah_factor) = match self {
+            Operator::Beginner => (0.25, 0.6, 0.1, 1.5),
+            Operator::Intermediate => (0.1, 0.4, 0.02, 1.3),
+            Operator::Expert => (0.02, 0.0, 0.0, 1.0),
+        };
+
+        TimingErrors {
+            spread,
+            correlation,
+            long_dah_chance,
+            long_dah_factor,
+        }
+    }
+}
+
+/// The sending style of a simulated operator, producing the factor by
+/// which each element or space deviates from its ideal length.
+pub struct Fist {
+    errors: TimingErrors,
+    rng: StdRng,
+    error: f32,
+}
+
+impl Fist {
+    pub fn new(operator: Operator, rng: StdRng) -> Self {
+        Self {
+            errors: operator.timing_errors(),
+            rng,
+            error: 0.0,
+        }
+    }
+
+    /// A sample of the standard normal distribution (Box-Muller).
+    fn normal(&mut self) -> f32 {
+        let u1: f32 = self.rng.gen_range(f32::EPSILON..1.0);
+        let u2: f32 = self.rng.gen();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+
+    /// The factor of the next element or space's length.
+    pub fn next_factor(&mut self, is_dah: bool) -> f32 {
+        let TimingErrors {
+            spread,
+            correlation,
+            ..
+        } = self.errors;
+        let innovation = (1.0 - correlation * correlation).sqrt() * spread * self.normal();
+        self.error = correlation * self.error + innovation;
+
+        let mut factor = (1.0 + self.error).max(0.2);
+        if is_dah && self.rng.gen_bool(self.errors.long_dah_chance) {
+            factor *= self.errors.long_dah_factor;
+        }
+
+        factor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// The variance of the factors of many elements sent by the operator.
+    fn variance(operator: Operator) -> f32 {
+        let mut fist = Fist::new(operator, StdRng::seed_from_u64(1));
+        let factors = (0..10_000)
+            .map(|index| fist.next_factor(index % 2 == 0))
+            .collect::<Vec<_>>();
+        let mean = factors.iter().sum::<f32>() / factors.len() as f32;
+
+        factors
+            .iter()
+            .map(|factor| (factor - mean).powi(2))
+            .sum::<f32>()
+            / factors.len() as f32
+    }
+
+    #[test]
+    fn beginners_vary_more_than_experts() {
+        let (beginner, expert) = (variance(Operator::Beginner), variance(Operator::Expert));
+        assert!(beginner > 10.0 * expert, "{} {}", beginner, expert);
+        assert!(variance(Operator::Intermediate) < beginner);
+    }
+}
@@ -0,0 +1,23 @@
+use rand::{seq::SliceRandom, Rng};
+
+/// The order in which the Koch method introduces characters.
+pub const KOCH_ORDER: &str = "KMURESNAPTLWI.JZ=FOY,VG5/Q92H38B?47C1D60X";
+
+/// The characters learned up to and including a Koch lesson. Lesson 1
+/// already consists of two characters.
+pub fn koch_charset(lesson: usize) -> String {
+    KOCH_ORDER.chars().take(lesson + 1).collect()
+}
+
+/// Generates `count` groups of `size` characters drawn from `charset`.
+pub fn random_groups(rng: &mut impl Rng, charset: &str, count: usize, size: usize) -> Vec<String> {
+    let charset = charset.chars().collect::<Vec<_>>();
+
+    (0..count)
+        .map(|_| {
+            (0..size)
+                .filter_map(|_| charset.choose(rng))
+                .collect::<String>()
+        })
+        .collect()
+}
@@ -0,0 +1,159 @@
+use crate::decode::GapThresholds;
+use std::str::FromStr;
+
+/// How an iambic keyer treats a squeeze that is released during an
+/// element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IambicMode {
+    /// The paddles are only read between elements, so the keyer stops
+    /// after the element during which they were released.
+    A,
+    /// The keyer also remembers that the opposite paddle was pressed
+    /// during an element, so it sends one more element after a release.
+    B,
+}
+
+impl FromStr for IambicMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use IambicMode::*;
+
+        match s.to_lowercase().as_str() {
+            "a" => Ok(A),
+            "b" => Ok(B),
+            _ => Err(format!("unknown iambic mode: {}", s)),
+        }
+    }
+}
+
+/// The state of the paddles during one unit of time.
+#[derive(Debug, Clone, Copy, Default)]
+struct Paddles {
+    dit: bool,
+    dah: bool,
+}
+
+impl Paddles {
+    /// The paddles written as `.` for the dit paddle, `-` for the dah
+    /// paddle, `+` for both, and `_` or a space for neither.
+    fn from_char(c: char) -> Option<Self> {
+        let (dit, dah) = match c {
+            '.' => (true, false),
+            '-' => (false, true),
+            '+' => (true, true),
+            '_' | ' ' => (false, false),
+            _ => return None,
+        };
+
+        Some(Self { dit, dah })
+    }
+
+    /// Whether the paddle of the other element than `dah` is pressed.
+    fn opposite(self, dah: bool) -> bool {
+        if dah {
+            self.dit
+        } else {
+            self.dah
+        }
+    }
+}
+
+/// Whether a character of a paddle pattern stands for a state of the
+/// paddles.
+pub fn is_paddle(c: char) -> bool {
+    Paddles::from_char(c).is_some()
+}
+
+/// Simulates an iambic keyer. The pattern holds the state of the paddles
+/// for every unit of time, and other characters are ignored. Held paddles
+/// send a stream of their element, and squeezed ones alternating elements,
+/// starting with a dit. Elements are read back as dot/dash code, with the
+/// silences between them classified by the gap thresholds.
+pub fn key(pattern: &str, mode: IambicMode, gaps: GapThresholds) -> String {
+    let paddles = pattern
+        .chars()
+        .filter_map(Paddles::from_char)
+        .collect::<Vec<_>>();
+    let at = |time: usize| paddles.get(time).copied().unwrap_or_default();
+
+    let mut code = String::new();
+    let mut time = 0;
+    // The last element, as whether it was a dah.
+    let mut last: Option<bool> = None;
+    // The end of the space after the last element.
+    let mut idle_since = 0;
+    // Whether the opposite paddle was pressed during the last element, in
+    // mode B.
+    let mut memory = false;
+    while time < paddles.len() || memory {
+        let now = at(time);
+        let dah = if memory {
+            !last.unwrap()
+        } else if now.dit && now.dah {
+            // A squeeze alternates with the element before it, or starts
+            // with a dit.
+            time == idle_since && last == Some(false)
+        } else if now.dit || now.dah {
+            now.dah
+        } else {
+            time += 1;
+            continue;
+        };
+
+        if last.is_some() {
+            // The symbol space after the last element counts as a unit.
+            let gap = (time - idle_since + 1) as f32;
+            if gap >= gaps.word {
+                code.push_str(" / ");
+            } else if gap >= gaps.letter {
+                code.push(' ');
+            }
+        }
+        code.push(if dah { '-' } else { '.' });
+
+        let len = if dah { 3 } else { 1 };
+        memory = mode == IambicMode::B && (time..time + len).any(|time| at(time).opposite(dah));
+        last = Some(dah);
+        time += len + 1;
+        idle_since = time;
+    }
+
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyed(pattern: &str, mode: IambicMode) -> String {
+        key(pattern, mode, GapThresholds::default())
+    }
+
+    #[test]
+    fn held_paddles_send_the_same_in_both_modes() {
+        for mode in [IambicMode::A, IambicMode::B] {
+            assert_eq!(keyed("...", mode), "..");
+            assert_eq!(keyed("----", mode), "-");
+            assert_eq!(keyed("..__--", mode), ". -");
+        }
+    }
+
+    #[test]
+    fn mode_b_sends_one_more_element_after_a_squeeze_release() {
+        // Squeezed for the dit, its space, and the start of the dah.
+        assert_eq!(keyed("+++", IambicMode::A), ".-");
+        assert_eq!(keyed("+++", IambicMode::B), ".-.");
+
+        // Released during the dit, so only mode B remembers the dah paddle.
+        assert_eq!(keyed("+", IambicMode::A), ".");
+        assert_eq!(keyed("+", IambicMode::B), ".-");
+    }
+
+    #[test]
+    fn parses_the_mode_case_insensitively() {
+        assert_eq!("a".parse(), Ok(IambicMode::A));
+        assert_eq!("B".parse(), Ok(IambicMode::B));
+        assert!("c".parse::<IambicMode>().is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use crate::decode::{DecodeOptions, StreamDecoder};
+use anyhow::{bail, Context, Result};
+use rodio::cpal::{
+    self,
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    FromSample, SizedSample,
+};
+use std::{
+    io::Write,
+    sync::mpsc::{self, Sender},
+};
+
+/// Decodes Morse code from the default input device in real time, printing
+/// characters as they resolve. Runs until interrupted.
+pub fn listen(options: &DecodeOptions) -> Result<()> {
+    let device = cpal::default_host()
+        .default_input_device()
+        .context("no input device available")?;
+    let config = device.default_input_config()?;
+    let sample_rate = config.sample_rate().0;
+    let (sender, receiver) = mpsc::channel();
+
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => input_stream::<f32>(&device, &config.into(), sender)?,
+        cpal::SampleFormat::I16 => input_stream::<i16>(&device, &config.into(), sender)?,
+        cpal::SampleFormat::U16 => input_stream::<u16>(&device, &config.into(), sender)?,
+        format => bail!("unsupported input sample format: {}", format),
+    };
+    stream.play()?;
+
+    let mut decoder = StreamDecoder::new(options, sample_rate);
+    let mut stdout = std::io::stdout();
+    for samples in receiver {
+        let resolved = decoder.push(&samples);
+        if !resolved.is_empty() {
+            print!("{}", resolved);
+            stdout.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Opens an input stream that sends the samples, mixed down to mono,
+/// through the channel.
+fn input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    sender: Sender<Vec<f32>>,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let samples = data
+                .chunks(channels)
+                .map(|frame| {
+                    frame
+                        .iter()
+                        .map(|sample| sample.to_sample::<f32>())
+                        .sum::<f32>()
+                        / channels as f32
+                })
+                .collect();
+            // The receiver only goes away when the program exits.
+            let _ = sender.send(samples);
+        },
+        |error| eprintln!("error: input stream failed: {}", error),
+        None,
+    )?;
+
+    Ok(stream)
+}
@@ -1,31 +1,357 @@
-use anyhow::Result;
-use rodio::{OutputStream, Source};
+mod abbreviation;
+mod alphabet;
+mod background;
+mod config;
+mod decode;
+mod effects;
+mod envelope;
+mod fist;
+mod groups;
+mod keyer;
+mod listen;
+mod pitch;
+mod preview;
+mod rig;
+mod separator;
+mod sidecar;
+mod template;
+mod timing;
+mod visual;
+mod waveform;
+
+use abbreviation::abbreviate;
+use alphabet::{decode_code, encode_text, encode_words, unknown_chars, Alphabet, PROSIGNS};
+use anyhow::{bail, Context, Result};
+use background::Background;
+use config::Profile;
+use decode::{
+    decode_pcm, decode_wav, decode_wav_plot, grade_wav, median_dit, split_overs, text,
+    DecodeFormat, DecodeOptions, GapThresholds,
+};
+use envelope::{Accent, Curve, Envelope, SidetoneShape};
+use fist::{Fist, Operator};
+use groups::{koch_charset, random_groups};
+use keyer::IambicMode;
+use pitch::{read_pitch_table, PitchTable};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rig::Rig;
+use separator::SeparatorStyle;
+use rodio::{buffer::SamplesBuffer, source::Amplify, OutputStream, Source};
 use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    fmt,
     fs::File,
-    path::PathBuf,
+    io::{BufWriter, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
     thread::sleep,
-    time::Duration, f32::consts::PI,
+    time::{Duration, Instant}, f32::consts::PI,
 };
+use sidecar::{decoded_json, manifest, table, unknown_report, Card, Metadata, Plan};
+use template::{Fields, OutputTemplate};
+use timing::Timing;
+use waveform::{Tone, Waveform};
+
+/// The sample rate of rendered audio files.
+const SAMPLE_RATE: u32 = 44100;
 
 struct Args {
     frequency: f32,
+    frequency_range: Option<FrequencyRange>,
+    /// A table of the frequency each character is sent at.
+    pitch_per_char: Option<PathBuf>,
     unit: f32,
+    weight: f32,
+    /// The length of the space between letters, in units.
+    letter_space: f32,
+    /// The length of the space between words, in units.
+    word_space: f32,
+    envelope: Envelope,
+    /// A custom envelope that every tone is additionally shaped with.
+    envelope_curve: Option<Curve>,
+    waveform: Waveform,
+    /// The share of each period a square wave spends at the high level.
+    duty: f32,
+    /// Whether the tone sounds during the spaces instead of the elements.
+    invert: bool,
+    /// Whether every element starts at a random phase instead of zero.
+    phase_randomize: bool,
+    /// The gains of the elements by their position within a letter.
+    accent: Option<Accent>,
+    /// The amplitude of the tone, from 0 to 1.
+    volume: f32,
+    /// The level of the tone that leaks through while the key is up, as a
+    /// fraction of the volume.
+    key_up_level: f32,
+    /// How many milliseconds after the start of an element the tone starts,
+    /// like a transmitter's keying relay.
+    tx_delay: f32,
+    /// How many milliseconds after the end of an element the tone stops.
+    tx_tail: f32,
+    /// Whether the output ends with the last tone instead of a space.
+    no_trailing_gap: bool,
+    /// Exact lengths of the rendered dits and dahs in samples, overriding
+    /// the unit, the weight, and the simulated fist.
+    dit_samples: Option<u64>,
+    dah_samples: Option<u64>,
+    /// The level, in dB below full scale, under which the output is
+    /// silenced, including anything mixed in.
+    gate: Option<f32>,
+    /// The integrated loudness, in LUFS, the output is normalized to.
+    target_lufs: Option<f32>,
+    /// How many milliseconds the whole output fades in and out over.
+    fade_in: f32,
+    fade_out: f32,
+    operator: Option<Operator>,
+    tolerant_spacing: bool,
+    /// The mode of the iambic keyer that the messages are paddle patterns
+    /// for, if they are.
+    iambic: Option<IambicMode>,
+    /// How the words of dot/dash code are separated, or `None` to detect
+    /// it from the code.
+    separator_style: Option<SeparatorStyle>,
+    /// Whether word breaks are sent as letter spaces, running the whole
+    /// input together like a single word.
+    no_word_split: bool,
+    /// The marker that joins the letters around it into one character.
+    prosign_char: char,
     morse_code: String,
+    infile: Option<PathBuf>,
+    url: Option<String>,
+    text: bool,
+    /// Whether common words of text are replaced with their abbreviations.
+    abbreviate: bool,
+    alphabet: Alphabet,
+    groups: Option<usize>,
+    group_size: usize,
+    charset: String,
+    seed: Option<u64>,
+    channels: u16,
+    stereo_delay: f32,
+    reverb: Option<f32>,
+    /// A recording mixed underneath rendered files.
+    background: Option<PathBuf>,
+    /// A recording the rendered Morse code is mixed onto once, after the
+    /// gate.
+    overlay: Option<PathBuf>,
+    /// Whether the channels of the background and the overlay are averaged
+    /// before mixing.
+    mono_mixdown: bool,
+    /// The slope of the high shelf applied to the rendered samples, in dB
+    /// per octave.
+    pre_emphasis: Option<f32>,
+    test_tone: Option<f32>,
+    batch: Option<PathBuf>,
+    force: bool,
+    count_only: bool,
+    /// Whether the encoded messages are printed before they are sent.
+    print_code: bool,
+    /// Whether the messages are wrapped in the greeting and the sign-off,
+    /// which are text.
+    qso_wrap: bool,
+    greeting: String,
+    sign_off: String,
+    /// Whether the resolved settings are printed as JSON instead of sending.
+    dry_run_json: bool,
+    /// Whether the characters that can't be sent are listed as JSON.
+    report_unknown: bool,
+    sidecar: bool,
+    preview: bool,
+    haptic: Option<PathBuf>,
+    /// Whether every word is rendered to its own file.
+    split_words: bool,
+    /// The longest file, in seconds, that the output is split into.
+    max_file_seconds: Option<f32>,
+    /// The number of frames that the length of rendered files is padded to
+    /// a multiple of.
+    align: Option<usize>,
+    /// The longest message, in seconds, that is played or rendered.
+    max_duration: Option<f32>,
+    /// The length, in seconds, that the speed is chosen to fit the message
+    /// into.
+    fit_duration: Option<f32>,
+    /// The length, in seconds, to repeat the message up to.
+    repeat_until: Option<f32>,
+    /// The silence after each repetition in seconds, a word space by default.
+    message_gap: Option<f32>,
+    /// Whether playback repeats until interrupted.
+    loop_forever: bool,
+    /// Whether each character is printed as it is played.
+    echo: bool,
+    /// A callsign sent between repetitions to identify the station.
+    id: Option<String>,
+    /// The time, in seconds, after which the callsign is sent again.
+    id_interval: f32,
+    visual: bool,
+    /// The number of columns the visual timeline is wrapped to, the width
+    /// of the terminal by default.
+    visual_width: Option<usize>,
+    /// Whether the visual timeline is colored. Only honored on terminals.
+    color: bool,
+    decode: bool,
+    /// Whether dot/dash messages are printed in canonical form instead of
+    /// being sent.
+    format_code: bool,
+    suggest: bool,
+    decode_wav: Option<PathBuf>,
+    decode_confidence: bool,
+    decode_format: DecodeFormat,
+    /// Whether a histogram of the durations in the recording is printed
+    /// after the decoded text.
+    decode_plot: bool,
+    /// Whether the decoder swaps dits and dahs.
+    decode_swap: bool,
+    /// Whether the decoder follows changes of the speed.
+    adaptive: bool,
+    /// Whether decoded recordings are split into overs at their prosigns.
+    split_on_prosign: bool,
+    decode_bandwidth: f32,
+    /// Whether the decoder weights its analysis blocks with a Hann window.
+    decode_window: bool,
+    /// How far, in Hz, the tone of a decoded recording may be from the
+    /// frequency.
+    frequency_tolerance: f32,
+    /// The length, in milliseconds, below which the decoder ignores the
+    /// segments of a recording.
+    min_element_ms: f32,
+    threshold: f32,
+    dit_dah_threshold: f32,
+    gap_thresholds: GapThresholds,
+    listen: bool,
+    decode_pcm: bool,
+    /// The sample rate of PCM read from stdin.
+    sample_rate: u32,
+    /// A lower sample rate to render and play at, for previewing quickly.
+    preview_rate: Option<u32>,
+    /// The sample rate rendered files are resampled to.
+    resample_output: Option<u32>,
+    list_prosigns: bool,
+    self_test: bool,
+    /// Whether a single dit is rendered to measure the clicks at its edges.
+    click_test: bool,
+    /// A message to time the rendering of.
+    bench: Option<String>,
+    iterations: u32,
+    /// A recording to decode and render again with the current settings.
+    transcode: Option<PathBuf>,
+    /// A recording to measure the speed of.
+    analyze_wpm: Option<PathBuf>,
+    /// A recording to grade the timing of.
+    grade: Option<PathBuf>,
+    /// A file to write the codes of the alphabet and the prosigns to.
+    export_table: Option<PathBuf>,
+    /// A directory to render every letter and digit of the alphabet to.
+    flashcards: Option<PathBuf>,
+    /// The format of the outfile, `wav` or `raw`, regardless of its
+    /// extension. MP3 and FLAC aren't supported in this build.
+    output_format: Option<OutputFormat>,
+    output_template: Option<OutputTemplate>,
+    play: bool,
     outfile: Option<PathBuf>,
 }
 
-#[derive(Debug)]
-enum MorseCode {
+/// A range of tone frequencies, written as `<low>:<high>` in Hz.
+#[derive(Debug, Clone, Copy)]
+struct FrequencyRange {
+    low: f32,
+    high: f32,
+}
+
+impl FromStr for FrequencyRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (low, high) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected <low>:<high>, got {}", s))?;
+        let low = low.trim().parse::<f32>().map_err(|e| e.to_string())?;
+        let high = high.trim().parse::<f32>().map_err(|e| e.to_string())?;
+        if !(0.0 < low && low <= high) {
+            return Err(format!("invalid frequency range: {}", s));
+        }
+
+        Ok(Self { low, high })
+    }
+}
+
+impl FrequencyRange {
+    fn sample(self, rng: &mut impl Rng) -> f32 {
+        rng.gen_range(self.low..=self.high)
+    }
+}
+
+/// Parses a duration in seconds, optionally suffixed with `s`, or in
+/// minutes suffixed with `m`, e.g. `300s` or `5m`.
+fn parse_seconds(s: &str) -> Result<f32, String> {
+    let (number, factor) = match s.strip_suffix('m') {
+        Some(minutes) => (minutes, 60.0),
+        None => (s.strip_suffix('s').unwrap_or(s), 1.0),
+    };
+    let number = number.trim().parse::<f32>().map_err(|e| e.to_string())?;
+
+    Ok(number * factor)
+}
+
+/// The file formats audio can be rendered to. Compressed formats aren't
+/// supported in this build.
+#[derive(Debug, Clone, Copy)]
+enum OutputFormat {
+    Wav,
+    /// Headerless little-endian 16 bit PCM.
+    Raw,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use OutputFormat::*;
+
+        match s.to_lowercase().as_str() {
+            "wav" => Ok(Wav),
+            "raw" | "pcm" => Ok(Raw),
+            "mp3" | "flac" => Err(format!("{} output is not supported in this build", s)),
+            _ => Err(format!("unsupported output format: {}", s)),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Picks the format from the file extension, defaulting to WAV.
+    fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.parse().ok())
+            .unwrap_or(OutputFormat::Wav)
+    }
+
+    /// The usual file extension of the format.
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Wav => "wav",
+            OutputFormat::Raw => "raw",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MorseCode {
     Dah,
     Dit,
 }
 
-#[derive(Debug)]
-enum Instruction {
+#[derive(Debug, Clone, Copy)]
+pub enum Instruction {
     Morse(MorseCode),
     SymbolSpace,
     LetterSpace,
     WordSpace,
+    /// Switches the tone of the following elements to another frequency.
+    Frequency(f32),
+    /// A silence of the given number of seconds, regardless of the speed.
+    Pause(f32),
 }
 
 impl From<MorseCode> for Instruction {
@@ -48,31 +374,697 @@ impl TryFrom<char> for MorseCode {
     }
 }
 
-fn main() {
-    let args = parse_args().unwrap();
-    let morse_code = parse_morse_code(&args.morse_code);
+/// What [`render_to_samples`] renders with: the arguments, and the
+/// background and overlay, which are read up front so that rendering
+/// doesn't touch the filesystem.
+pub struct RenderConfig<'a> {
+    args: &'a Args,
+    background: Option<Background>,
+    overlay: Option<Background>,
+}
+
+impl<'a> RenderConfig<'a> {
+    /// Reads the background and overlay of the arguments.
+    fn new(args: &'a Args) -> Result<Self> {
+        let read = |path: &Option<PathBuf>| {
+            path.as_ref()
+                .map(|path| Background::read(path, args.output_rate(), args.mono_mixdown))
+                .transpose()
+        };
+
+        Ok(Self {
+            args,
+            background: read(&args.background)?,
+            overlay: read(&args.overlay)?,
+        })
+    }
+}
+
+/// A part of the playback of the instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Playback {
+    Message,
+    /// The identification with the callsign.
+    Id,
+    /// The gap between messages.
+    Gap,
+}
+
+/// The exit codes of the program, chosen by the class of error:
+///
+/// - 0: success
+/// - 1: any other error
+/// - 2: invalid arguments
+/// - 3: the audio device couldn't be opened or played on
+/// - 4: reading or writing a file failed
+#[derive(Debug, Clone, Copy)]
+enum ExitStatus {
+    Generic = 1,
+    Usage = 2,
+    Audio = 3,
+    Io = 4,
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExitStatus::Generic => "error",
+            ExitStatus::Usage => "invalid arguments",
+            ExitStatus::Audio => "audio device error",
+            ExitStatus::Io => "file error",
+        })
+    }
+}
+
+impl ExitStatus {
+    /// Classifies an error by the status attached to it as context, or
+    /// otherwise by the type of its causes.
+    fn of(error: &anyhow::Error) -> Self {
+        if let Some(status) = error.downcast_ref::<ExitStatus>() {
+            return *status;
+        }
+
+        error
+            .chain()
+            .find_map(|cause| {
+                if cause.is::<pico_args::Error>() {
+                    Some(ExitStatus::Usage)
+                } else if cause.is::<std::io::Error>() || cause.is::<hound::Error>() {
+                    Some(ExitStatus::Io)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(ExitStatus::Generic)
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {:#}", error);
+            ExitCode::from(ExitStatus::of(&error) as u8)
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    let mut args = parse_args()?;
+    if args.list_prosigns {
+        print_prosigns();
+        return Ok(());
+    }
+
+    if args.self_test {
+        return self_test(&args);
+    }
+
+    if args.click_test {
+        return click_test(&args);
+    }
+
+    if let Some(message) = &args.bench {
+        return bench(&args, message);
+    }
+
+    if let Some(path) = &args.decode_wav {
+        return print_decoded(&args, path);
+    }
+
+    if let Some(path) = &args.analyze_wpm {
+        return analyze_wpm(&args, path);
+    }
+
+    if let Some(path) = &args.grade {
+        return grade(&args, path);
+    }
+
+    if let Some(path) = &args.export_table {
+        return export_table(&args, path);
+    }
+
+    if args.decode {
+        return print_decoded_code(&args);
+    }
+
+    if args.format_code {
+        for code in message_codes(&args, &messages(&args)?) {
+            println!("{}", formatted_code(&args, &code));
+        }
+        return Ok(());
+    }
+
+    if args.listen {
+        return listen::listen(&args.decode_options()).context(ExitStatus::Audio);
+    }
+
+    if args.decode_pcm {
+        let stdin = std::io::stdin().lock();
+        return decode_pcm(stdin, args.sample_rate, &args.decode_options(), std::io::stdout());
+    }
+
+    if let Some(batch) = &args.batch {
+        return render_batch(&args, batch);
+    }
+
+    if let Some(path) = &args.transcode {
+        return transcode(&args, path);
+    }
+
+    if let Some(dir) = &args.flashcards {
+        return render_flashcards(&args, dir);
+    }
+
+    let messages = messages(&args)?;
+    let mut codes = message_codes(&args, &messages);
+    if args.qso_wrap {
+        wrap_qso(&args, &mut codes);
+    }
+    if args.print_code {
+        println!("{}", printed_code(&codes));
+    }
+    let morse_code = match &args.pitch_per_char {
+        Some(path) => concat_pitched(&args, &messages, &read_pitch_table(path)?),
+        None => concat_messages(&args, &codes),
+    };
+    if let Some(seconds) = args.fit_duration {
+        args.unit = fit_unit(&args, &morse_code, seconds)?;
+    }
+    let morse_code = match args.repeat_until {
+        Some(seconds) => repeat_until(&args, &morse_code, seconds)?,
+        None => morse_code,
+    };
+    let result = send(&args, &codes, &morse_code);
+    if args.report_unknown {
+        println!("{}", unknown_report(&unknown_characters(&args, &messages)));
+    }
+
+    result
+}
+
+/// Sends the instructions of the encoded messages in the selected mode.
+fn send(args: &Args, codes: &[String], morse_code: &[Instruction]) -> Result<()> {
+    if args.count_only {
+        print_counts(morse_code);
+        return Ok(());
+    }
+
+    if let Some(path) = &args.haptic {
+        return write_haptic(args, morse_code, path);
+    }
+
+    if args.visual {
+        let width = args.visual_width.unwrap_or_else(preview::terminal_width);
+        println!("{}", visual::timeline(morse_code, args.color, width));
+        return Ok(());
+    }
+
+    if args.preview {
+        let samples = render_mono(args, morse_code)?;
+        println!("{}", preview::meter(&samples, preview::terminal_width()));
+        return Ok(());
+    }
+
+    let outfile = args.output_template.as_ref().map_or(args.outfile.clone(), |template| {
+        Some(template.expand(&Fields {
+            date: &template::today(),
+            index: 1,
+            wpm: args.wpm(),
+            text: &args.morse_code,
+        }))
+    });
+    if args.dry_run_json {
+        print_plan(args, morse_code, outfile.as_deref());
+        return Ok(());
+    }
+
+    check_duration(args, morse_code)?;
+    if args.split_words {
+        return render_parts(args, &split_words(morse_code));
+    }
+    if let Some(seconds) = args.max_file_seconds {
+        return render_parts(args, &split_chunks(args, morse_code, seconds)?);
+    }
 
-    if args.outfile.is_some() {
-        render_audio(&args, &morse_code);
+    if let Some(path) = &outfile {
+        let frames = render_audio(args, morse_code, path)?;
+        if args.sidecar {
+            let frequency = args.frequency_range.is_none().then_some(args.frequency);
+            write_sidecar(args, path, &codes.join(" / "), frequency, &frames)?;
+        }
+        if args.play {
+            play_frames(args, frames).context(ExitStatus::Audio)?;
+        }
     } else {
-        play_audio(&args, &morse_code).expect("failed to render morse code");
+        play_audio(args, morse_code).context(ExitStatus::Audio)?;
     }
+
+    Ok(())
 }
 
 fn parse_args() -> Result<Args> {
-    let mut pargs = pico_args::Arguments::from_env();
-    let args = Args {
+    parse_args_from(pico_args::Arguments::from_env())
+}
+
+/// Parses the arguments. Errors are invalid arguments, unless reading a
+/// file they name failed.
+fn parse_args_from(pargs: pico_args::Arguments) -> Result<Args> {
+    read_args(pargs).map_err(|error| match ExitStatus::of(&error) {
+        ExitStatus::Io => error,
+        _ => error.context(ExitStatus::Usage),
+    })
+}
+
+fn read_args(mut pargs: pico_args::Arguments) -> Result<Args> {
+    let config: Option<PathBuf> = pargs.opt_value_from_str("--config")?;
+    let profile = match pargs.opt_value_from_str::<_, String>("--profile")? {
+        Some(name) => {
+            let Some(path) = config.or_else(config::default_path) else {
+                bail!("no config file to load profile {} from", name);
+            };
+            config::load_profile(&path, &name)?
+        }
+        None => Profile::default(),
+    };
+    // A rig preset takes precedence over the profile, but not over the flags.
+    let profile = match pargs.opt_value_from_str::<_, Rig>("--rig")? {
+        Some(rig) => rig.profile().or(profile),
+        None => profile,
+    };
+    let unit = match (
+        pargs.opt_value_from_str(["-u", "--unit"])?,
+        pargs.opt_value_from_str::<_, f32>("--wpm")?,
+    ) {
+        (Some(_), Some(_)) => bail!("a unit and a speed in WPM can't be combined"),
+        (Some(unit), None) => unit,
+        (None, Some(wpm)) => unit_of_wpm(wpm),
+        (None, None) => profile
+            .unit
+            .or(profile.wpm.map(unit_of_wpm))
+            .unwrap_or(0.3),
+    };
+    let groups = pargs.opt_value_from_str("--groups")?;
+    let alphabet: Alphabet = pargs.opt_value_from_str("--alphabet")?.unwrap_or_default();
+    let test_tone = pargs.opt_value_from_str("--test-tone")?;
+    let batch: Option<PathBuf> = pargs.opt_value_from_str("--batch")?;
+    let decode_wav: Option<PathBuf> = pargs.opt_value_from_str("--decode-wav")?;
+    let infile: Option<PathBuf> = pargs.opt_value_from_str("--infile")?;
+    let url: Option<String> = pargs.opt_value_from_str("--url")?;
+    let listen = pargs.contains("--listen");
+    let decode_pcm = pargs.contains("--decode-pcm");
+    let list_prosigns = pargs.contains("--list-prosigns");
+    let self_test = pargs.contains("--self-test");
+    let click_test = pargs.contains("--click-test");
+    let bench: Option<String> = pargs.opt_value_from_str("--bench")?;
+    let flashcards: Option<PathBuf> = pargs.opt_value_from_str("--flashcards")?;
+    let transcode: Option<PathBuf> = pargs.opt_value_from_str("--transcode")?;
+    let analyze_wpm: Option<PathBuf> = pargs.opt_value_from_str("--analyze-wpm")?;
+    let grade: Option<PathBuf> = pargs.opt_value_from_str("--grade")?;
+    let export_table: Option<PathBuf> = pargs.opt_value_from_str("--export-table")?;
+    // Random groups, the test tone, batches, decoding, input files, URLs,
+    // listening, the prosign list, the self-test, the click test,
+    // benchmarks, flashcards, transcoding, speed analysis, grading, and the
+    // table export replace the message, so it is optional for them.
+    let message_optional = groups.is_some()
+        || test_tone.is_some()
+        || batch.is_some()
+        || decode_wav.is_some()
+        || infile.is_some()
+        || url.is_some()
+        || listen
+        || decode_pcm
+        || list_prosigns
+        || self_test
+        || click_test
+        || bench.is_some()
+        || flashcards.is_some()
+        || transcode.is_some()
+        || analyze_wpm.is_some()
+        || grade.is_some()
+        || export_table.is_some();
+    let mut args = Args {
         frequency: pargs
-            .value_from_str(["-f", "--frequency"])
-            .unwrap_or_else(|_| 440.0),
-        unit: pargs
-            .value_from_str(["-u", "--unit"])
-            .unwrap_or_else(|_| 0.3),
-        morse_code: pargs.free_from_str()?,
+            .opt_value_from_str(["-f", "--frequency"])?
+            .or(profile.frequency)
+            .unwrap_or(440.0),
+        frequency_range: pargs.opt_value_from_str("--freq-range")?,
+        pitch_per_char: pargs.opt_value_from_str("--pitch-per-char")?,
+        unit,
+        weight: pargs
+            .opt_value_from_str(["-w", "--weight"])?
+            .unwrap_or(50.0),
+        letter_space: pargs.opt_value_from_str("--letter-space")?.unwrap_or(3.0),
+        word_space: pargs.opt_value_from_str("--word-space")?.unwrap_or(7.0),
+        invert: pargs.contains("--invert"),
+        phase_randomize: pargs.contains("--phase-randomize"),
+        accent: pargs.opt_value_from_str("--accent")?,
+        envelope: pargs
+            .opt_value_from_str("--sidetone-shape")?
+            .or(profile.sidetone_shape)
+            .map(SidetoneShape::envelope)
+            .unwrap_or_default(),
+        envelope_curve: pargs
+            .opt_value_from_str::<_, PathBuf>("--envelope")?
+            .map(|path| Curve::read(&path))
+            .transpose()?,
+        waveform: pargs
+            .opt_value_from_str("--waveform")?
+            .or(profile.waveform)
+            .unwrap_or_default(),
+        duty: pargs.opt_value_from_str("--duty")?.unwrap_or(0.5),
+        volume: pargs
+            .opt_value_from_str("--volume")?
+            .or(profile.volume)
+            .unwrap_or(1.0),
+        key_up_level: pargs.opt_value_from_str("--key-up-level")?.unwrap_or(0.0),
+        tx_delay: pargs.opt_value_from_str("--tx-delay")?.unwrap_or(0.0),
+        tx_tail: pargs.opt_value_from_str("--tx-tail")?.unwrap_or(0.0),
+        dit_samples: pargs.opt_value_from_str("--dit-samples")?,
+        dah_samples: pargs.opt_value_from_str("--dah-samples")?,
+        gate: pargs.opt_value_from_str("--gate")?,
+        target_lufs: pargs.opt_value_from_str("--target-lufs")?,
+        fade_in: pargs.opt_value_from_str("--fade-in")?.unwrap_or(0.0),
+        fade_out: pargs.opt_value_from_str("--fade-out")?.unwrap_or(0.0),
+        no_trailing_gap: pargs.contains("--no-trailing-gap"),
+        operator: pargs.opt_value_from_str("--operator")?,
+        tolerant_spacing: pargs.contains("--tolerant-spacing"),
+        iambic: pargs.opt_value_from_str("--iambic")?,
+        separator_style: pargs.opt_value_from_str("--separator-style")?,
+        no_word_split: pargs.contains("--no-word-split"),
+        prosign_char: pargs.opt_value_from_str("--prosign-char")?.unwrap_or('_'),
+        infile,
+        url,
+        text: pargs.contains(["-t", "--text"]),
+        abbreviate: pargs.contains("--abbreviate"),
+        alphabet,
+        groups,
+        group_size: pargs.opt_value_from_str("--group-size")?.unwrap_or(5),
+        charset: match pargs.opt_value_from_str("--koch")? {
+            Some(lesson) => koch_charset(lesson),
+            None => pargs
+                .opt_value_from_str("--charset")?
+                .unwrap_or_else(|| alphabet.alphanumeric()),
+        },
+        seed: pargs.opt_value_from_str("--seed")?,
+        channels: match pargs.opt_value_from_str("--channels")? {
+            Some(channels) => channels,
+            None if pargs.contains("--stereo") => 2,
+            None => 1,
+        },
+        stereo_delay: pargs.opt_value_from_str("--stereo-delay")?.unwrap_or(0.0),
+        reverb: pargs.opt_value_from_str("--reverb")?,
+        background: pargs.opt_value_from_str("--background")?,
+        overlay: pargs.opt_value_from_str("--overlay")?,
+        mono_mixdown: pargs.contains("--mono-mixdown"),
+        pre_emphasis: pargs
+            .opt_value_from_str("--pre-emphasis")?
+            .or(profile.pre_emphasis),
+        test_tone,
+        batch,
+        output_format: pargs
+            .opt_value_from_str("--output-format")?
+            .or(profile.output_format),
+        output_template: pargs.opt_value_from_str("--output-template")?,
+        play: pargs.contains("--play"),
+        force: pargs.contains("--force"),
+        count_only: pargs.contains("--count-only"),
+        print_code: pargs.contains("--print-code"),
+        qso_wrap: pargs.contains("--qso-wrap"),
+        greeting: pargs
+            .opt_value_from_str("--greeting")?
+            .unwrap_or_else(|| String::from("CQ CQ DE")),
+        sign_off: pargs
+            .opt_value_from_str("--sign-off")?
+            .unwrap_or_else(|| String::from("<AR>")),
+        dry_run_json: pargs.contains("--dry-run-json"),
+        report_unknown: pargs.contains("--report-unknown"),
+        sidecar: pargs.contains("--sidecar"),
+        preview: pargs.contains("--preview"),
+        haptic: pargs.opt_value_from_str("--haptic")?,
+        split_words: pargs.contains("--split-words"),
+        max_file_seconds: pargs.opt_value_from_fn("--max-file-seconds", parse_seconds)?,
+        align: pargs.opt_value_from_str("--align")?,
+        max_duration: pargs.opt_value_from_str("--max-duration")?,
+        fit_duration: pargs.opt_value_from_fn("--fit-duration", parse_seconds)?,
+        repeat_until: pargs.opt_value_from_fn("--repeat-until", parse_seconds)?,
+        message_gap: pargs.opt_value_from_str("--message-gap")?,
+        loop_forever: pargs.contains("--loop-forever"),
+        echo: pargs.contains("--echo"),
+        id: pargs.opt_value_from_str("--id")?,
+        id_interval: pargs
+            .opt_value_from_fn("--id-interval", parse_seconds)?
+            .unwrap_or(600.0),
+        visual: pargs.contains("--visual"),
+        visual_width: pargs.opt_value_from_str("--visual-width")?,
+        color: pargs.contains("--color") && std::io::stdout().is_terminal(),
+        decode: pargs.contains("--decode"),
+        format_code: pargs.contains("--format-code"),
+        suggest: pargs.contains("--suggest"),
+        decode_wav,
+        decode_confidence: pargs.contains("--decode-confidence"),
+        decode_plot: pargs.contains("--decode-plot"),
+        decode_format: pargs
+            .opt_value_from_str("--decode-format")?
+            .unwrap_or(DecodeFormat::Text),
+        decode_swap: pargs.contains("--decode-swap"),
+        adaptive: pargs.contains("--adaptive"),
+        split_on_prosign: pargs.contains("--split-on-prosign"),
+        decode_bandwidth: pargs
+            .opt_value_from_str("--decode-bandwidth")?
+            .unwrap_or(200.0),
+        decode_window: pargs.contains("--decode-window"),
+        frequency_tolerance: pargs
+            .opt_value_from_str("--frequency-tolerance")?
+            .unwrap_or(0.0),
+        min_element_ms: pargs.opt_value_from_str("--min-element-ms")?.unwrap_or(0.0),
+        threshold: pargs.opt_value_from_str("--threshold")?.unwrap_or(0.5),
+        dit_dah_threshold: pargs
+            .opt_value_from_str("--dit-dah-threshold")?
+            .unwrap_or(2.0),
+        gap_thresholds: pargs
+            .opt_value_from_str("--gap-thresholds")?
+            .unwrap_or_default(),
+        listen,
+        decode_pcm,
+        sample_rate: pargs
+            .opt_value_from_str("--sample-rate")?
+            .unwrap_or(SAMPLE_RATE),
+        preview_rate: pargs.opt_value_from_str("--preview-rate")?,
+        resample_output: pargs.opt_value_from_str("--resample-output")?,
+        list_prosigns,
+        self_test,
+        click_test,
+        bench,
+        iterations: pargs.opt_value_from_str("--iterations")?.unwrap_or(10),
+        transcode,
+        analyze_wpm,
+        grade,
+        export_table,
+        flashcards,
         outfile: pargs.opt_value_from_str(["-o", "--outfile"])?,
+        morse_code: String::new(),
     };
 
-    let remaining = pargs.finish();
+    if !(args.weight > 0.0 && args.weight < 100.0) {
+        bail!("weight must be between 0 and 100 percent");
+    }
+    if !(args.unit > 0.0 && args.unit.is_finite()) {
+        bail!("the unit must be positive");
+    }
+    if !(args.letter_space >= 1.0 && args.word_space >= args.letter_space) {
+        bail!("the letter space must be at least 1 unit and the word space at least as long");
+    }
+    if args.fit_duration.is_some_and(|seconds| !(seconds > 0.0 && seconds.is_finite())) {
+        bail!("the duration to fit the message into must be positive");
+    }
+    if args.fit_duration.is_some() && (args.repeat_until.is_some() || args.operator.is_some()) {
+        bail!("fitting the duration can't be combined with repetitions or a simulated operator");
+    }
+    if args.repeat_until.is_some_and(|seconds| !(seconds > 0.0 && seconds.is_finite())) {
+        bail!("the duration to repeat until must be positive");
+    }
+    if args.message_gap.is_some_and(|seconds| !(seconds >= 0.0 && seconds.is_finite())) {
+        bail!("the message gap must not be negative");
+    }
+    if args.id.is_some() && !(args.loop_forever || args.repeat_until.is_some()) {
+        bail!("identifying the station requires looping forever or repeating until a duration");
+    }
+    if !(args.id_interval > 0.0 && args.id_interval.is_finite()) {
+        bail!("the identification interval must be positive");
+    }
+    if matches!(args.decode_format, DecodeFormat::Json)
+        && (args.decode_confidence || args.decode_plot || args.split_on_prosign)
+    {
+        bail!("the JSON decode format already includes the confidence and can't be split or plotted");
+    }
+    if args.max_duration.is_some_and(|seconds| seconds <= 0.0) {
+        bail!("the maximum duration must be positive");
+    }
+    if ![args.tx_delay, args.tx_tail].iter().all(|ms| *ms >= 0.0 && ms.is_finite()) {
+        bail!("the transmitter delay and tail must be finite and not negative");
+    }
+    if args.dit_samples == Some(0) || args.dah_samples == Some(0) {
+        bail!("the dit and dah sample counts must be positive");
+    }
+    if args.gate.is_some_and(|db| db > 0.0 || db.is_nan()) {
+        bail!("the gate must be at most 0 dB");
+    }
+    if args.target_lufs.is_some_and(|lufs| !lufs.is_finite()) {
+        bail!("the target loudness must be a finite number");
+    }
+    if !(args.fade_in >= 0.0 && args.fade_out >= 0.0) {
+        bail!("the fade in and out must not be negative");
+    }
+    if !(0.0..=1.0).contains(&args.volume) {
+        bail!("the volume must be between 0 and 1");
+    }
+    if !(0.0..=1.0).contains(&args.key_up_level) {
+        bail!("the key-up level must be between 0 and 1");
+    }
+    if !(args.duty > 0.0 && args.duty < 1.0) {
+        bail!("the duty cycle must be between 0 and 1");
+    }
+    if args.test_tone.is_some_and(|seconds| !(seconds > 0.0 && seconds.is_finite())) {
+        bail!("the test tone duration must be positive and finite");
+    }
+    if !(0.0..1.0).contains(&args.threshold) {
+        bail!("the threshold must be at least 0 and less than 1");
+    }
+    if !(args.dit_dah_threshold > 1.0 && args.dit_dah_threshold < 3.0) {
+        bail!("the dit/dah threshold must be between 1 and 3 units");
+    }
+    if args.iterations == 0 {
+        bail!("the number of iterations must be positive");
+    }
+    if args.sample_rate == 0 {
+        bail!("the sample rate must be positive");
+    }
+    if args.preview_rate.is_some_and(|rate| !(1000..=SAMPLE_RATE).contains(&rate)) {
+        bail!("the preview rate must lie between 1000 and {} Hz", SAMPLE_RATE);
+    }
+    if args.resample_output.is_some_and(|rate| !(1000..=192_000).contains(&rate)) {
+        bail!("the output can only be resampled to between 1000 and 192000 Hz");
+    }
+    if args.preview_rate.is_some_and(|rate| args.frequency >= rate as f32 / 2.0) {
+        bail!("the frequency must be below half the preview rate");
+    }
+    if args.decode_bandwidth <= 0.0 {
+        bail!("the decode bandwidth must be positive");
+    }
+    if !(args.frequency_tolerance >= 0.0 && args.frequency_tolerance.is_finite()) {
+        bail!("the frequency tolerance must not be negative");
+    }
+    if !(args.min_element_ms >= 0.0 && args.min_element_ms.is_finite()) {
+        bail!("the minimum element length must not be negative");
+    }
+    if args.outfile.is_some() && args.output_template.is_some() {
+        bail!("an outfile and an output template can't be combined");
+    }
+    if args.batch.is_some() && args.outfile.is_none() && args.output_template.is_none() {
+        bail!("batch mode requires an outfile or an output template");
+    }
+    if args.background.is_some()
+        && args.outfile.is_none()
+        && args.output_template.is_none()
+        && args.batch.is_none()
+        && args.flashcards.is_none()
+    {
+        bail!("mixing a background requires an outfile");
+    }
+    if args.overlay.is_some()
+        && args.outfile.is_none()
+        && args.output_template.is_none()
+        && args.batch.is_none()
+        && args.flashcards.is_none()
+    {
+        bail!("overlaying a recording requires an outfile");
+    }
+    if args.loop_forever && (args.outfile.is_some() || args.output_template.is_some()) {
+        bail!("looping forever only applies to playback without an outfile");
+    }
+    if args.echo && (args.outfile.is_some() || args.output_template.is_some()) {
+        bail!("echoing only applies to playback without an outfile");
+    }
+    if args.transcode.is_some() && args.outfile.is_none() {
+        bail!("transcoding requires an outfile");
+    }
+    if args.split_words && args.outfile.is_none() && args.output_template.is_none() {
+        bail!("splitting words requires an outfile or an output template");
+    }
+    if args.iambic.is_some() && (args.text || args.groups.is_some()) {
+        bail!("paddle patterns for the iambic keyer can't be text");
+    }
+    if args.abbreviate && !args.text {
+        bail!("abbreviating requires text input");
+    }
+    if args.pitch_per_char.is_some() && !(args.text || args.groups.is_some()) {
+        bail!("pitch per character requires text input");
+    }
+    if args.pitch_per_char.is_some() && args.qso_wrap {
+        bail!("pitch per character can't be combined with wrapping the QSO");
+    }
+    if args.pitch_per_char.is_some() && args.frequency_range.is_some() {
+        bail!("pitch per character can't be combined with a frequency range");
+    }
+    if args.split_words && args.batch.is_some() {
+        bail!("splitting words can't be combined with batch mode");
+    }
+    if args.visual_width == Some(0) {
+        bail!("the visual width must be positive");
+    }
+    if args.align == Some(0) {
+        bail!("the alignment must be positive");
+    }
+    if args.max_file_seconds.is_some_and(|seconds| !(seconds > 0.0 && seconds.is_finite())) {
+        bail!("the maximum file length must be positive");
+    }
+    if args.max_file_seconds.is_some() {
+        if args.outfile.is_none() && args.output_template.is_none() {
+            bail!("splitting the output into files requires an outfile or an output template");
+        }
+        if args.split_words || args.batch.is_some() || args.loop_forever {
+            bail!("splitting the output into files can't be combined with split words or batches");
+        }
+        if args.operator.is_some() {
+            bail!("the files can't be kept short with a simulated operator's varying timing");
+        }
+    }
+    if !(1..=8).contains(&args.channels) {
+        bail!("the number of channels must be between 1 and 8");
+    }
+    if args.reverb.is_some_and(|amount| !(0.0..=1.0).contains(&amount)) {
+        bail!("the reverb amount must be between 0 and 1");
+    }
+    if args.pre_emphasis.is_some_and(|slope| !slope.is_finite()) {
+        bail!("the pre-emphasis must be a finite number");
+    }
+    if !(args.stereo_delay >= 0.0 && args.stereo_delay.is_finite()) {
+        bail!("the stereo delay must be finite and not negative");
+    }
+    if args.charset.is_empty() {
+        bail!("the character set for random groups must not be empty");
+    }
+    if matches!(args.prosign_char, '.' | '-' | '/') || args.prosign_char.is_whitespace() {
+        bail!("the prosign marker must not be a dot, dash, slash, or whitespace");
+    }
+
+    // The free arguments form the message, joined with spaces, so that
+    // shell-split Morse needn't be quoted. Unknown options are left over.
+    let (words, remaining): (Vec<_>, Vec<_>) = pargs
+        .finish()
+        .into_iter()
+        .partition(|arg| !message_optional && !is_option(arg));
+    if !message_optional {
+        if words.is_empty() {
+            return Err(pico_args::Error::MissingArgument.into());
+        }
+        let words = words
+            .into_iter()
+            .map(|word| word.into_string().map_err(|_| pico_args::Error::NonUtf8Argument))
+            .collect::<Result<Vec<_>, _>>()?;
+        args.morse_code = words.join(" ");
+    }
     if !remaining.is_empty() {
         eprintln!("warning: dangling arguments: {:?}", remaining);
     }
@@ -80,123 +1072,3000 @@ fn parse_args() -> Result<Args> {
     Ok(args)
 }
 
-fn parse_morse_code(code: &str) -> Vec<Instruction> {
-    let letters = code.split(' '); // [.-..-, .--.-]
-    let letters: Vec<Vec<Instruction>> = letters
-        .into_iter()
-        .map(|word| {
-            word.chars()
-                .into_iter()
-                .map(TryFrom::try_from)
-                .flatten()
-                .map(Instruction::Morse)
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
-    let number_of_letters = letters.len() - 1;
-    let mut res = Vec::new();
+/// Whether a left-over argument looks like a long option rather than a word
+/// of the message. Dashes followed by a letter are never Morse code.
+fn is_option(arg: &OsStr) -> bool {
+    arg.to_str().is_some_and(|arg| {
+        arg.strip_prefix("--")
+            .is_some_and(|name| name.starts_with(|c: char| c.is_ascii_alphabetic()))
+    })
+}
 
-    // Iterate over each letter the user inputs.
-    for (index, letter) in letters.into_iter().enumerate() {
-        // Iterate over each morse code char of said letter.
-        // Insert the morse code instruction followed by a
-        // symbol pause if another morse code instruction
-        // follows.
-        let letter_code_len = letter.len() - 1;
-        for (index_inner, morse_code) in letter.into_iter().enumerate() {
-            res.push(morse_code);
+/// The length of a unit in seconds at a speed in words per minute, measured
+/// by the 50 units of "PARIS ".
+fn unit_of_wpm(wpm: f32) -> f32 {
+    60.0 / (50.0 * wpm)
+}
 
-            if index_inner < letter_code_len {
-                res.push(Instruction::SymbolSpace);
-            }
-        }
+impl Args {
+    /// The simulated operator's timing errors, if any.
+    fn fist(&self) -> Option<Fist> {
+        self.operator.map(|operator| Fist::new(operator, self.rng()))
+    }
 
-        // We finished encoding an ASCII letter. Insert a
-        // letter space instruction if another morse code
-        // instruction follows.
-        if index < number_of_letters {
-            res.push(Instruction::LetterSpace);
+    fn decode_options(&self) -> DecodeOptions {
+        DecodeOptions {
+            frequency: self.frequency,
+            bandwidth: self.decode_bandwidth,
+            window: self.decode_window,
+            tolerance: self.frequency_tolerance,
+            min_element: self.min_element_ms / 1000.0,
+            threshold: self.threshold,
+            dit_dah: self.dit_dah_threshold,
+            gaps: self.gap_thresholds,
+            swap: self.decode_swap,
+            adaptive: self.adaptive,
+            alphabet: self.alphabet,
         }
     }
 
-    res
-}
+    /// A random number generator that is reproducible if a seed was given.
+    fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
 
-fn play_audio(args: &Args, ins: &[Instruction]) -> Result<()> {
-    let (_stream, stream_handle) = OutputStream::try_default()?;
-    let sink = rodio::Sink::try_new(&stream_handle)?;
+    /// The speed in words per minute, the reverse of [`unit_of_wpm`].
+    fn wpm(&self) -> u32 {
+        (60.0 / (50.0 * self.unit)).round() as u32
+    }
 
-    let tone = rodio::source::SineWave::new(args.frequency);
-    let dot_duration = Duration::from_millis((args.unit * 1000.) as u64);
-    let dot = tone.clone().take_duration(dot_duration);
-    let dash = tone.take_duration(dot_duration * 3);
+    /// How the words of dot/dash code are separated. The style is only
+    /// detected if the spaces don't carry meaning of their own, as they do
+    /// with tolerant spacing and when formatting code.
+    fn separator_style(&self, code: &str) -> SeparatorStyle {
+        self.separator_style.unwrap_or_else(|| {
+            if self.tolerant_spacing || self.format_code {
+                SeparatorStyle::Slash
+            } else {
+                SeparatorStyle::detect(code)
+            }
+        })
+    }
 
-    for is in ins {
-        use Instruction::*;
+    /// The space sent between words.
+    fn word_break(&self) -> Instruction {
+        if self.no_word_split {
+            Instruction::LetterSpace
+        } else {
+            Instruction::WordSpace
+        }
+    }
 
-        match is {
-            Morse(c) => match c {
-                MorseCode::Dit => {
-                    sink.append(dot.clone());
-                    sink.sleep_until_end();
-                }
-                MorseCode::Dah => {
-                    sink.append(dash.clone());
-                    sink.sleep_until_end();
-                }
-            },
-            SymbolSpace => sleep(dot_duration),
-            LetterSpace => sleep(dot_duration * 3),
-            _ => unreachable!(),
+    /// The instructions without the spaces at their end, if those are to be
+    /// dropped.
+    fn trimmed<'a>(&self, ins: &'a [Instruction]) -> &'a [Instruction] {
+        if !self.no_trailing_gap {
+            return ins;
         }
+
+        let end = ins
+            .iter()
+            .rposition(|is| matches!(is, Instruction::Morse(_)))
+            .map_or(0, |index| index + 1);
+        &ins[..end]
     }
 
-    Ok(())
-}
+    /// Whether playback needs the rendered samples. Only the sample
+    /// generator knows how to invert the keying, accent elements, randomize
+    /// their phase, leak the tone while the key is up, shape tones with a
+    /// sidetone or custom envelope, and gate them, and emphasis, reverb,
+    /// fades and loudness normalization need the whole output.
+    fn needs_rendering(&self) -> bool {
+        self.invert
+            || self.key_up_level > 0.0
+            || self.phase_randomize
+            || self.accent.is_some()
+            || self.envelope.attack > 0.0
+            || self.envelope.release > 0.0
+            || self.envelope_curve.is_some()
+            || self.gate.is_some()
+            || self.pre_emphasis.is_some()
+            || self.reverb.is_some()
+            || self.target_lufs.is_some()
+            || self.fade_in > 0.0
+            || self.fade_out > 0.0
+    }
 
-fn render_audio(args: &Args, ins: &[Instruction]) {
-    fn wav_sleep(writer: &mut hound::WavWriter<std::io::BufWriter<File>>, samples: u64) {
-        for _ in (0..samples).map(|x| x as f32 / 44100.0) {
-            writer.write_sample(0).unwrap();
-        }
+    /// The sample rate audio is rendered and played at.
+    fn output_rate(&self) -> u32 {
+        self.preview_rate.unwrap_or(SAMPLE_RATE)
     }
-    
-    let path = args.outfile.as_deref().unwrap();
-    let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate: 44100,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
-    let mut writer = hound::WavWriter::create(path, spec).unwrap();
 
-    let dit_samples = spec.sample_rate as f32 / args.unit;
-    let dit_samples = dit_samples as u64;
-    let dah_samples = dit_samples * 3;
+    /// The sample rate of rendered files.
+    fn file_rate(&self) -> u32 {
+        self.resample_output.unwrap_or(self.output_rate())
+    }
 
-    for is in ins {
-        use Instruction::*;
+    /// An endless tone for playback.
+    fn tone(&self, frequency: f32) -> Amplify<Tone> {
+        Tone::new(self.waveform, self.duty, frequency, self.output_rate()).amplify(self.volume)
+    }
 
-        match is {
-            Morse(c) => match c {
-                MorseCode::Dit => {
-                    for t in (0..dit_samples).map(|x| x as f32 / 44100.0) {
-                        let sample = (t * args.frequency * 2.0 * PI).sin();
-                        let amplitude = i16::MAX as f32;
-                        writer.write_sample((sample * amplitude) as i16).unwrap();
-                    }
-                }
-                MorseCode::Dah => {
-                    for t in (0..dah_samples).map(|x| x as f32 / 44100.0) {
-                        let sample = (t * args.frequency * 2.0 * PI).sin();
-                        let amplitude = i16::MAX as f32;
-                        writer.write_sample((sample * amplitude) as i16).unwrap();
-                    }
-                }
-            },
-            SymbolSpace => wav_sleep(&mut writer, dit_samples),
-            LetterSpace => wav_sleep(&mut writer, dah_samples),
-            _ => unreachable!(),
-        }
+    /// The durations of the elements and spaces.
+    fn timing(&self) -> Timing {
+        let mut timing = Timing::new(self.unit, self.weight, self.letter_space, self.word_space);
+        let seconds = |samples: u64| samples as f32 / self.output_rate() as f32;
+        timing.dit = self.dit_samples.map_or(timing.dit, seconds);
+        timing.dah = self.dah_samples.map_or(timing.dah, seconds);
+
+        timing
+    }
+}
+
+/// Encodes a pangram, renders it with the current settings, and checks that
+/// decoding the audio yields the pangram again.
+fn self_test(args: &Args) -> Result<()> {
+    const PANGRAM: &str = "THE QUICK BROWN FOX JUMPS OVER THE LAZY DOG";
+
+    let code = encode_text(PANGRAM, Alphabet::Latin);
+    let samples = render_mono(args, &parse_morse_code(&code))?
+        .into_iter()
+        .map(|sample| sample as f32 / i16::MAX as f32)
+        .collect::<Vec<_>>();
+    let options = DecodeOptions {
+        alphabet: Alphabet::Latin,
+        ..args.decode_options()
+    };
+    let decoded = text(&decode::decode(&samples, args.output_rate(), &options));
+
+    if decoded != PANGRAM {
+        bail!("self-test failed: expected {}, decoded {}", PANGRAM, decoded);
+    }
+    println!("self-test passed");
+    Ok(())
+}
+
+/// Prints the click metrics of a single dit, as measured by [`clicks`].
+fn click_test(args: &Args) -> Result<()> {
+    let (edge, steady) = clicks(args)?;
+    println!("edge step: {:.4}", edge);
+    println!("steady step: {:.4}", steady);
+    Ok(())
+}
+
+/// Renders a single dit with the current settings and returns the largest
+/// step between two samples within a millisecond of its edges, as a crude
+/// measure of how loudly it clicks. The largest step within the steady part
+/// of the tone follows for comparison. Both are relative to full scale.
+fn clicks(args: &Args) -> Result<(f32, f32)> {
+    let dit = [Instruction::Morse(MorseCode::Dit), Instruction::SymbolSpace];
+    let samples = render_mono(args, &dit)?;
+    let (Some(start), Some(end)) = (
+        samples.iter().position(|sample| *sample != 0),
+        samples.iter().rposition(|sample| *sample != 0),
+    ) else {
+        bail!("the dit is silent");
+    };
+
+    // The steps into the first sample and out of the last one count, too.
+    let steps = std::iter::once(0)
+        .chain(samples.iter().copied())
+        .chain(std::iter::once(0))
+        .collect::<Vec<_>>()
+        .windows(2)
+        .map(|pair| (pair[1] as f32 - pair[0] as f32).abs() / 32768.0)
+        .collect::<Vec<_>>();
+    let window = (args.output_rate() / 1000) as usize;
+    let near_edge =
+        |index: usize| index.abs_diff(start) <= window || index.abs_diff(end + 1) <= window;
+    let peak = |edge: bool| {
+        steps
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| near_edge(*index) == edge)
+            .map(|(_, step)| *step)
+            .fold(0.0, f32::max)
+    };
+
+    Ok((peak(true), peak(false)))
+}
+
+/// Prints the render speed of a message, as measured by [`throughput`].
+fn bench(args: &Args, message: &str) -> Result<()> {
+    let (average, throughput) = throughput(args, message)?;
+    println!("average render time: {:.3} ms", 1000.0 * average);
+    println!("throughput: {:.0} samples/s", throughput);
+    Ok(())
+}
+
+/// Renders a message into memory repeatedly and returns the average time a
+/// render takes in seconds and the number of samples rendered per second.
+fn throughput(args: &Args, message: &str) -> Result<(f64, f64)> {
+    let ins = parse_instructions(args, &encode_message(args, message));
+    let start = Instant::now();
+    let mut samples = 0;
+    for _ in 0..args.iterations {
+        samples += render_mono(args, &ins)?.len();
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    Ok((elapsed / args.iterations as f64, samples as f64 / elapsed))
+}
+
+/// Prints the prosigns that can be sent by name, their code, and their
+/// meaning.
+fn print_prosigns() {
+    print!("{}", prosign_list());
+}
+
+/// The lines printed by [`print_prosigns`].
+fn prosign_list() -> String {
+    PROSIGNS
+        .iter()
+        .map(|(name, code, meaning)| format!("{:<4} {:<9} {}\n", name, code, meaning))
+        .collect()
+}
+
+/// Writes the codes of the characters of the alphabet and of the prosigns
+/// as JSON. Prosigns are named in angle brackets, the way they are written
+/// in text.
+fn export_table(args: &Args, path: &Path) -> Result<()> {
+    let mut entries: Vec<(String, &str)> = Vec::new();
+    for (c, code) in args.alphabet.entries() {
+        // Only the first code of a character is ever sent.
+        if !entries.iter().any(|(name, _)| *name == c.to_string()) {
+            entries.push((c.to_string(), code));
+        }
+    }
+    entries.extend(PROSIGNS.iter().map(|(name, code, _)| (format!("<{}>", name), *code)));
+    std::fs::write(path, table(&entries))?;
+
+    Ok(())
+}
+
+/// Decodes a recording and prints the text, followed by the code and
+/// confidence score of each character and the histogram of durations if
+/// requested. With a frequency tolerance, the detected frequency is
+/// reported first, on stderr.
+fn print_decoded(args: &Args, path: &Path) -> Result<()> {
+    let (characters, histogram, frequency) = decode_wav_plot(path, &args.decode_options())?;
+    if args.frequency_tolerance > 0.0 {
+        eprintln!("tone detected at {:.0} Hz", frequency);
+    }
+    if let DecodeFormat::Json = args.decode_format {
+        println!("{}", decoded_json(&characters));
+        return Ok(());
+    }
+    if args.split_on_prosign {
+        println!("{}", split_overs(&characters));
+    } else {
+        println!("{}", text(&characters));
+    }
+
+    if args.decode_confidence {
+        for character in characters.iter().filter(|character| !character.code.is_empty()) {
+            println!(
+                "{} {} {:.0}",
+                character.value, character.code, character.confidence
+            );
+        }
+    }
+    if args.decode_plot {
+        print!("{}", histogram);
+    }
+
+    Ok(())
+}
+
+/// Measures the median dit of a recording and prints the speed it
+/// corresponds to.
+fn analyze_wpm(args: &Args, path: &Path) -> Result<()> {
+    let Some(dit) = median_dit(path, &args.decode_options())? else {
+        bail!("no elements detected in {}", path.display());
+    };
+    println!("{:.1} WPM (dit {:.1} ms)", 1.2 / dit, dit * 1000.0);
+
+    Ok(())
+}
+
+/// Grades the timing of a recording and prints the deviation of each kind
+/// of element and gap and the overall fist quality.
+fn grade(args: &Args, path: &Path) -> Result<()> {
+    let Some(grade) = grade_wav(path, &args.decode_options())? else {
+        bail!("no elements detected in {}", path.display());
+    };
+    println!("unit {:.1} ms ({:.1} WPM)", grade.unit * 1000.0, 1.2 / grade.unit);
+    for (name, count, deviation) in &grade.kinds {
+        println!("{:<12} {:>4} off by {:.1}%", name, count, deviation * 100.0);
+    }
+    println!("fist quality {:.0}%", grade.quality);
+
+    Ok(())
+}
+
+/// Decodes a recording and renders the decoded code to the outfile, at the
+/// current speed and in the current format. Unknown codes are sent as
+/// they were received.
+fn transcode(args: &Args, path: &Path) -> Result<()> {
+    let characters = decode_wav(path, &args.decode_options())?;
+    let code = characters
+        .iter()
+        .map(|character| match character.code.as_str() {
+            "" => "/",
+            code => code,
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let outfile = args.outfile.as_deref().unwrap();
+    let morse_code = parse_instructions(args, &code);
+    check_duration(args, &morse_code)?;
+    let frames = render_audio(args, &morse_code, outfile)?;
+    if args.sidecar {
+        write_sidecar(args, outfile, &code, Some(args.frequency), &frames)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the resolved settings for sending the instructions as JSON.
+fn print_plan(args: &Args, ins: &[Instruction], outfile: Option<&Path>) {
+    print!("{}", plan(args, ins, outfile).to_json());
+}
+
+/// The resolved settings for sending the instructions.
+fn plan<'a>(args: &Args, ins: &[Instruction], outfile: Option<&'a Path>) -> Plan<'a> {
+    let format = outfile.map(|path| {
+        args.output_format
+            .unwrap_or_else(|| OutputFormat::from_path(path))
+            .extension()
+    });
+
+    Plan {
+        frequency: args.frequency_range.is_none().then_some(args.frequency),
+        timing: args.timing(),
+        outfile,
+        format,
+        play: outfile.is_none() || args.play,
+        duration: total_duration(args, ins),
+        instructions: ins.len(),
+    }
+}
+
+/// Prints the number of words, letters, and elements of the instructions.
+fn print_counts(ins: &[Instruction]) {
+    let (words, letters, elements) = counts(ins);
+    println!("words: {}", words);
+    println!("letters: {}", letters);
+    println!("elements: {}", elements);
+}
+
+/// The number of words, letters, and elements of the instructions.
+fn counts(ins: &[Instruction]) -> (usize, usize, usize) {
+    // Spaces after the last element don't separate anything.
+    let end = ins
+        .iter()
+        .rposition(|is| matches!(is, Instruction::Morse(_)))
+        .map_or(0, |index| index + 1);
+    let ins = &ins[..end];
+    let count = |predicate: fn(&Instruction) -> bool| ins.iter().filter(|is| predicate(is)).count();
+    let elements = count(|is| matches!(is, Instruction::Morse(_)));
+    let word_spaces = count(|is| matches!(is, Instruction::WordSpace | Instruction::Pause(_)));
+    let letter_spaces = count(|is| matches!(is, Instruction::LetterSpace));
+
+    // Every space separates two words or letters, unless nothing is sent.
+    if elements == 0 {
+        (0, 0, 0)
+    } else {
+        (word_spaces + 1, word_spaces + letter_spaces + 1, elements)
+    }
+}
+
+/// The alternating key-down and key-up periods of the instructions, in
+/// seconds. Adjacent periods of the same state are merged.
+fn keying(args: &Args, ins: &[Instruction]) -> Vec<(bool, f32)> {
+    let timing = args.timing();
+    let mut fist = args.fist();
+    let mut periods: Vec<(bool, f32)> = Vec::new();
+
+    for is in args.trimmed(ins) {
+        use Instruction::*;
+
+        let (on, seconds, is_dah) = match is {
+            Morse(MorseCode::Dit) => (true, timing.dit, false),
+            Morse(MorseCode::Dah) => (true, timing.dah, true),
+            SymbolSpace => (false, timing.symbol_space, false),
+            LetterSpace => (false, timing.letter_space, false),
+            WordSpace => (false, timing.word_space, false),
+            Frequency(_) => continue,
+            Pause(seconds) => (false, *seconds, false),
+        };
+        let seconds = match is {
+            Pause(_) => seconds,
+            _ => seconds * fist.as_mut().map_or(1.0, |fist| fist.next_factor(is_dah)),
+        };
+        match periods.last_mut() {
+            Some((last_on, last_seconds)) if *last_on == on => *last_seconds += seconds,
+            _ => periods.push((on, seconds)),
+        }
+    }
+
+    periods
+}
+
+/// The estimated duration of the instructions in seconds. With a
+/// simulated operator and no seed, the actual duration differs slightly.
+fn total_duration(args: &Args, ins: &[Instruction]) -> f32 {
+    match args.test_tone {
+        Some(seconds) => seconds,
+        None => keying(args, ins).iter().map(|(_, seconds)| seconds).sum(),
+    }
+}
+
+/// The unit that makes the instructions last `seconds`. Every duration is
+/// proportional to the unit, except for pauses and exact sample counts.
+fn fit_unit(args: &Args, ins: &[Instruction], seconds: f32) -> Result<f32> {
+    let timing = Timing::new(1.0, args.weight, args.letter_space, args.word_space);
+    let exact = |samples: u64| samples as f32 / args.output_rate() as f32;
+    let (mut units, mut fixed) = (0.0, 0.0);
+    for is in args.trimmed(ins) {
+        use Instruction::*;
+
+        match is {
+            Morse(MorseCode::Dit) => match args.dit_samples {
+                Some(samples) => fixed += exact(samples),
+                None => units += timing.dit,
+            },
+            Morse(MorseCode::Dah) => match args.dah_samples {
+                Some(samples) => fixed += exact(samples),
+                None => units += timing.dah,
+            },
+            SymbolSpace => units += timing.symbol_space,
+            LetterSpace => units += timing.letter_space,
+            WordSpace => units += timing.word_space,
+            Pause(seconds) => fixed += seconds,
+            Frequency(_) => {}
+        }
+    }
+    if !(units > 0.0 && seconds > fixed) {
+        bail!("the message can't be fitted into {} seconds", seconds);
+    }
+
+    Ok((seconds - fixed) / units)
+}
+
+/// The instructions of the callsign the station identifies with.
+fn id_instructions(args: &Args, callsign: &str) -> Vec<Instruction> {
+    parse_instructions(args, &encode_text(callsign, args.alphabet))
+}
+
+/// Repeats the instructions, each time followed by the message gap, as
+/// often as the repetitions fit into `seconds`. The result can be looped
+/// seamlessly. With a callsign, it is sent after the first repetition that
+/// ends at least the identification interval after the last time it
+/// started, or after the start.
+fn repeat_until(args: &Args, ins: &[Instruction], seconds: f32) -> Result<Vec<Instruction>> {
+    let gap = args.message_gap.unwrap_or(args.timing().word_space);
+    let period = total_duration(args, ins) + gap;
+    if period > seconds {
+        bail!(
+            "the message and its gap take about {:.1} seconds, longer than {} seconds",
+            period,
+            seconds
+        );
+    }
+    let id = args.id.as_ref().map(|callsign| {
+        let id = id_instructions(args, callsign);
+        let period = total_duration(args, &id) + gap;
+        (id, period)
+    });
+
+    let mut res = Vec::new();
+    let (mut elapsed, mut since_id) = (0.0, 0.0);
+    while elapsed + period <= seconds {
+        res.extend_from_slice(ins);
+        res.push(Instruction::Pause(gap));
+        elapsed += period;
+        since_id += period;
+
+        match &id {
+            Some((id, id_period)) if since_id >= args.id_interval => {
+                if elapsed + id_period > seconds {
+                    break;
+                }
+                res.extend_from_slice(id);
+                res.push(Instruction::Pause(gap));
+                elapsed += id_period;
+                since_id = *id_period;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(res)
+}
+
+/// Fails if the instructions take longer than the maximum duration.
+fn check_duration(args: &Args, ins: &[Instruction]) -> Result<()> {
+    let duration = total_duration(args, ins);
+    match args.max_duration {
+        Some(max_duration) if duration > max_duration => bail!(
+            "the message would take about {:.1} seconds, more than the maximum of {} seconds",
+            duration,
+            max_duration
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Writes the keying as a JSON array of alternating on and off durations in
+/// milliseconds, starting with an on duration, to drive a vibration motor.
+fn write_haptic(args: &Args, ins: &[Instruction], path: &Path) -> Result<()> {
+    let periods = match args.test_tone {
+        Some(seconds) => vec![(true, seconds)],
+        None => keying(args, ins),
+    };
+    let mut durations = Vec::new();
+    if periods.first().is_some_and(|(on, _)| !on) {
+        durations.push(0);
+    }
+    durations.extend(periods.iter().map(|(_, seconds)| (seconds * 1000.0).round() as u64));
+
+    let durations = durations.iter().map(u64::to_string).collect::<Vec<_>>();
+    std::fs::write(path, format!("[{}]\n", durations.join(", ")))?;
+    Ok(())
+}
+
+/// Decodes dot/dash messages and prints their text. With suggestions, every
+/// code without a character is followed by the closest valid codes.
+fn print_decoded_code(args: &Args) -> Result<()> {
+    for code in message_codes(args, &messages(args)?) {
+        println!("{}", decode_code(&code, args.alphabet));
+
+        if args.suggest {
+            let unknown = code
+                .split(|c: char| c == '/' || c.is_whitespace())
+                .filter(|letter| !letter.is_empty() && args.alphabet.decode(letter).is_none());
+            for letter in unknown {
+                let suggestions = args
+                    .alphabet
+                    .suggest(letter, 3)
+                    .into_iter()
+                    .map(|(character, code)| format!("{} {}", character, code))
+                    .collect::<Vec<_>>();
+                println!("{}: {}", letter, suggestions.join(", "));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The messages to send as they were given, either directly, read from a
+/// file or URL with one message per line, or generated as random groups.
+fn messages(args: &Args) -> Result<Vec<String>> {
+    let lines = |content: String| {
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned)
+            .collect()
+    };
+
+    if let Some(count) = args.groups {
+        let groups = random_groups(&mut args.rng(), &args.charset, count, args.group_size);
+        Ok(vec![groups.join(" ")])
+    } else if let Some(path) = &args.infile {
+        Ok(lines(read_infile(path)?))
+    } else if let Some(url) = &args.url {
+        Ok(lines(fetch_url(url)?))
+    } else {
+        Ok(vec![args.morse_code.clone()])
+    }
+}
+
+/// The dot/dash code of the messages to send. Random groups are always
+/// encoded from text.
+fn message_codes(args: &Args, messages: &[String]) -> Vec<String> {
+    messages
+        .iter()
+        .map(|message| match args.groups {
+            Some(_) => encode_text(message, args.alphabet),
+            None => encode_message(args, message),
+        })
+        .collect()
+}
+
+/// Prepends the code of the greeting to the first message and appends the
+/// code of the sign-off to the last one.
+fn wrap_qso(args: &Args, codes: &mut [String]) {
+    let greeting = encode_text(&args.greeting, args.alphabet);
+    let sign_off = encode_text(&args.sign_off, args.alphabet);
+    let join = |a: &str, b: &str| match (a.trim().is_empty(), b.trim().is_empty()) {
+        (true, _) => b.to_owned(),
+        (_, true) => a.to_owned(),
+        _ => format!("{} / {}", a, b),
+    };
+    if let Some(first) = codes.first_mut() {
+        *first = join(&greeting, first);
+    }
+    if let Some(last) = codes.last_mut() {
+        *last = join(last, &sign_off);
+    }
+}
+
+/// The characters of the messages that are dropped when they are encoded
+/// or parsed, as the index of their message, their offset in characters,
+/// and the character.
+fn unknown_characters(args: &Args, messages: &[String]) -> Vec<(usize, usize, char)> {
+    let mut unknown = Vec::new();
+    for (index, message) in messages.iter().enumerate() {
+        let characters = if args.text || args.groups.is_some() {
+            unknown_chars(message, args.alphabet)
+        } else if args.iambic.is_some() {
+            message
+                .chars()
+                .enumerate()
+                .filter(|(_, c)| !keyer::is_paddle(*c))
+                .collect()
+        } else {
+            let separators = args.separator_style(message).separator_offsets(message);
+            message
+                .chars()
+                .enumerate()
+                .filter(|(offset, c)| {
+                    !(c.is_whitespace()
+                        || *c == '/'
+                        || *c == args.prosign_char
+                        || MorseCode::try_from(*c).is_ok()
+                        || separators.contains(offset))
+                })
+                .collect()
+        };
+        unknown.extend(characters.into_iter().map(|(offset, c)| (index, offset, c)));
+    }
+
+    unknown
+}
+
+/// The codes of the messages as printed with `--print-code`, separated by
+/// word breaks.
+fn printed_code(codes: &[String]) -> String {
+    codes.join(" / ")
+}
+
+/// Downloads a message. Only successful responses with textual content are
+/// accepted.
+#[cfg(feature = "http")]
+fn fetch_url(url: &str) -> Result<String> {
+    let response = match ureq::get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(status, response)) => {
+            bail!("{} responded with {} {}", url, status, response.status_text())
+        }
+        Err(error) => return Err(error.into()),
+    };
+    if response.status() != 200 {
+        bail!("{} responded with {} {}", url, response.status(), response.status_text());
+    }
+    if !response.content_type().starts_with("text/") {
+        bail!("{} returned {} instead of text", url, response.content_type());
+    }
+
+    Ok(response.into_string()?)
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_url(_url: &str) -> Result<String> {
+    bail!("reading messages from URLs requires the http feature")
+}
+
+/// Parses the messages and joins them with word spaces. With a frequency
+/// range, each message is sent on its own random frequency.
+fn concat_messages(args: &Args, codes: &[String]) -> Vec<Instruction> {
+    let mut rng = args.rng();
+    let mut res = Vec::new();
+
+    for (index, code) in codes.iter().enumerate() {
+        if index > 0 {
+            res.push(args.word_break());
+        }
+        if let Some(range) = args.frequency_range {
+            res.push(Instruction::Frequency(range.sample(&mut rng)));
+        }
+        res.extend(parse_instructions(args, code));
+    }
+
+    res
+}
+
+/// Concatenates text messages like [`concat_messages`], sending every
+/// letter at the frequency of its character in the pitch table. Letters
+/// without a frequency, such as prosigns, are sent at the usual frequency.
+/// Messages are abbreviated first if requested.
+fn concat_pitched(args: &Args, messages: &[String], pitches: &PitchTable) -> Vec<Instruction> {
+    let mut res = Vec::new();
+
+    for (index, message) in messages.iter().enumerate() {
+        if index > 0 {
+            res.push(args.word_break());
+        }
+        let message = if args.abbreviate {
+            abbreviate(message)
+        } else {
+            message.to_owned()
+        };
+        for (index, word) in encode_words(&message, args.alphabet).iter().enumerate() {
+            if index > 0 {
+                res.push(args.word_break());
+            }
+            for (index, (character, code)) in word.iter().enumerate() {
+                if index > 0 {
+                    res.push(Instruction::LetterSpace);
+                }
+                let frequency = character
+                    .and_then(|character| character.to_uppercase().next())
+                    .and_then(|character| pitches.get(&character));
+                res.push(Instruction::Frequency(*frequency.unwrap_or(&args.frequency)));
+                res.extend(parse_morse_word(code));
+            }
+        }
+    }
+
+    res
+}
+
+/// Reads a message file, decompressing it first if it is gzipped.
+fn read_infile(path: &Path) -> Result<String> {
+    let content = std::fs::read(path)?;
+    let gzipped = path.extension().is_some_and(|extension| extension == "gz")
+        || content.starts_with(&[0x1f, 0x8b]);
+
+    if gzipped {
+        decompress_gzip(&content)
+    } else {
+        Ok(String::from_utf8(content)?)
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn decompress_gzip(content: &[u8]) -> Result<String> {
+    use std::io::Read;
+
+    let mut message = String::new();
+    flate2::read::GzDecoder::new(content).read_to_string(&mut message)?;
+    Ok(message)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn decompress_gzip(_content: &[u8]) -> Result<String> {
+    bail!("reading gzip-compressed files requires the gzip feature")
+}
+
+/// Encodes the message if it is text, keys it if it is a paddle pattern,
+/// or passes it through if it is already dot/dash code. Text is
+/// abbreviated first if requested.
+fn encode_message(args: &Args, message: &str) -> String {
+    if let Some(mode) = args.iambic {
+        keyer::key(message, mode, args.gap_thresholds)
+    } else if args.abbreviate {
+        encode_text(&abbreviate(message), args.alphabet)
+    } else if args.text {
+        encode_text(message, args.alphabet)
+    } else {
+        args.separator_style(message).normalize(message)
+    }
+}
+
+/// Renders each non-empty line of the batch file to its own file. Files
+/// that already exist are skipped unless forced, so an interrupted batch
+/// can be resumed by running it again.
+fn render_batch(args: &Args, batch: &Path) -> Result<()> {
+    let messages = std::fs::read_to_string(batch)?;
+    let messages = messages
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    let date = template::today();
+    let paths = messages
+        .iter()
+        .enumerate()
+        .map(|(index, message)| match &args.output_template {
+            Some(template) => template.expand(&Fields {
+                date: &date,
+                index: index + 1,
+                wpm: args.wpm(),
+                text: message,
+            }),
+            None => batch_path(args.outfile.as_deref().unwrap(), index + 1),
+        })
+        .collect::<Vec<_>>();
+    check_unique(&paths)?;
+    let codes = messages
+        .iter()
+        .map(|message| encode_message(args, message))
+        .collect::<Vec<_>>();
+    for code in &codes {
+        check_duration(args, &parse_instructions(args, code))?;
+    }
+    let mut rng = args.rng();
+
+    for (code, path) in codes.into_iter().zip(paths) {
+        // Draw the frequency even for skipped files so that a resumed
+        // batch assigns the same frequencies as an uninterrupted one.
+        let frequency = args.frequency_range.map(|range| range.sample(&mut rng));
+        if path.exists() && !args.force {
+            eprintln!("skipping {}: file already exists", path.display());
+            continue;
+        }
+
+        let mut morse_code = Vec::from_iter(frequency.map(Instruction::Frequency));
+        morse_code.extend(parse_instructions(args, &code));
+        let frames = render_audio(args, &morse_code, &path)?;
+        if args.sidecar {
+            let frequency = frequency.unwrap_or(args.frequency);
+            write_sidecar(args, &path, &code, Some(frequency), &frames)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders every letter and digit of the alphabet to its own file in
+/// `dir`, named after the character, and lists them in `manifest.json`.
+fn render_flashcards(args: &Args, dir: &Path) -> Result<()> {
+    let extension = args.output_format.unwrap_or(OutputFormat::Wav).extension();
+    std::fs::create_dir_all(dir)?;
+
+    let mut cards = Vec::new();
+    for (c, code) in args.alphabet.entries() {
+        if !c.is_alphanumeric() {
+            continue;
+        }
+
+        let file = format!("{}.{}", c, extension);
+        render_audio(args, &parse_instructions(args, code), &dir.join(&file))?;
+        cards.push(Card { character: c, file, code });
+    }
+    std::fs::write(dir.join("manifest.json"), manifest(&cards))?;
+
+    Ok(())
+}
+
+/// Renders each part of the instructions, such as a word, to its own file,
+/// named like the files of a batch.
+fn render_parts(args: &Args, parts: &[Vec<Instruction>]) -> Result<()> {
+    let codes = parts.iter().map(|part| code_of(part)).collect::<Vec<_>>();
+    let date = template::today();
+    let paths = codes
+        .iter()
+        .enumerate()
+        .map(|(index, code)| match &args.output_template {
+            Some(template) => template.expand(&Fields {
+                date: &date,
+                index: index + 1,
+                wpm: args.wpm(),
+                text: &decode_code(code, args.alphabet),
+            }),
+            None => batch_path(args.outfile.as_deref().unwrap(), index + 1),
+        })
+        .collect::<Vec<_>>();
+    check_unique(&paths)?;
+
+    for ((part, code), path) in parts.iter().zip(&codes).zip(&paths) {
+        let frames = render_audio(args, part, path)?;
+        if args.sidecar {
+            let frequency = part.iter().find_map(|is| match is {
+                Instruction::Frequency(frequency) => Some(*frequency),
+                _ => None,
+            });
+            let frequency = frequency.unwrap_or(args.frequency);
+            write_sidecar(args, path, code, Some(frequency), &frames)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits the instructions at their word spaces. A word whose frequency
+/// was changed by an earlier word starts with that frequency.
+fn split_words(ins: &[Instruction]) -> Vec<Vec<Instruction>> {
+    let mut words = Vec::new();
+    let mut word = Vec::new();
+    let mut frequency = None;
+
+    for is in ins {
+        match is {
+            Instruction::WordSpace | Instruction::Pause(_) => {
+                words.push(std::mem::take(&mut word))
+            }
+            Instruction::Frequency(new_frequency) => {
+                frequency = Some(*new_frequency);
+                if !word.is_empty() {
+                    word.push(*is);
+                }
+            }
+            is => {
+                if word.is_empty() {
+                    word.extend(frequency.map(Instruction::Frequency));
+                }
+                word.push(*is);
+            }
+        }
+    }
+    words.push(word);
+
+    words.retain(|word| word.iter().any(|is| matches!(is, Instruction::Morse(_))));
+    words
+}
+
+/// Splits the instructions into chunks that last at most `seconds` each,
+/// breaking only at letter and word spaces. The space at a break ends the
+/// chunk before it. A chunk whose frequency was changed by an earlier one
+/// starts with that frequency.
+fn split_chunks(args: &Args, ins: &[Instruction], seconds: f32) -> Result<Vec<Vec<Instruction>>> {
+    let timing = args.timing();
+    let duration = |is: &Instruction| match is {
+        Instruction::Morse(MorseCode::Dit) => timing.dit,
+        Instruction::Morse(MorseCode::Dah) => timing.dah,
+        Instruction::SymbolSpace => timing.symbol_space,
+        Instruction::LetterSpace => timing.letter_space,
+        Instruction::WordSpace => timing.word_space,
+        Instruction::Pause(seconds) => *seconds,
+        Instruction::Frequency(_) => 0.0,
+    };
+
+    let mut chunks = Vec::new();
+    let mut chunk: Vec<Instruction> = Vec::new();
+    let mut chunk_duration = 0.0;
+    let mut frequency = None;
+    let letters = ins.split_inclusive(|is| {
+        matches!(is, Instruction::LetterSpace | Instruction::WordSpace | Instruction::Pause(_))
+    });
+    for letter in letters {
+        let letter_duration = letter.iter().map(duration).sum::<f32>();
+        if letter_duration > seconds {
+            bail!(
+                "a letter with its space takes {:.1} seconds, longer than the maximum file length",
+                letter_duration
+            );
+        }
+        if chunk_duration + letter_duration > seconds && !chunk.is_empty() {
+            chunks.push(std::mem::take(&mut chunk));
+            chunk.extend(frequency.map(Instruction::Frequency));
+            chunk_duration = 0.0;
+        }
+        for is in letter {
+            if let Instruction::Frequency(new_frequency) = is {
+                frequency = Some(*new_frequency);
+            }
+        }
+        chunk.extend_from_slice(letter);
+        chunk_duration += letter_duration;
+    }
+    chunks.push(chunk);
+
+    chunks.retain(|chunk| chunk.iter().any(|is| matches!(is, Instruction::Morse(_))));
+    Ok(chunks)
+}
+
+/// The dot/dash code of the instructions.
+fn code_of(ins: &[Instruction]) -> String {
+    ins.iter()
+        .filter_map(|is| match is {
+            Instruction::Morse(MorseCode::Dit) => Some("."),
+            Instruction::Morse(MorseCode::Dah) => Some("-"),
+            Instruction::LetterSpace => Some(" "),
+            Instruction::WordSpace | Instruction::Pause(_) => Some(" / "),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Code in the canonical form printed with `--format-code`: its spacing read
+/// tolerantly, with single spaces between letters and ` / ` between words.
+fn formatted_code(args: &Args, code: &str) -> String {
+    let code = join_prosigns(code, args.prosign_char);
+    code_of(&parse_tolerant_morse_code(&code, args.gap_thresholds))
+}
+
+/// Fails if more than one file would be written to the same path.
+fn check_unique(paths: &[PathBuf]) -> Result<()> {
+    let mut unique = HashSet::new();
+    match paths.iter().find(|path| !unique.insert(*path)) {
+        Some(path) => bail!(
+            "the output template names more than one file {}; consider adding {{index}}",
+            path.display()
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Describes the rendered file at `path` in a JSON file next to it.
+fn write_sidecar(
+    args: &Args,
+    path: &Path,
+    code: &str,
+    frequency: Option<f32>,
+    frames: &[i16],
+) -> Result<()> {
+    Metadata {
+        text: &decode_code(code, args.alphabet),
+        code,
+        wpm: args.wpm(),
+        frequency,
+        samples: frames.len() / args.channels as usize,
+        sample_rate: args.file_rate(),
+    }
+    .write(path)
+}
+
+/// The path of the `index`th file of a batch: the outfile with the index
+/// appended to its name, e.g. `out_1.wav` for `out.wav`.
+fn batch_path(outfile: &Path, index: usize) -> PathBuf {
+    let stem = outfile.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match outfile.extension() {
+        Some(extension) => format!("{}_{}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}_{}", stem, index),
+    };
+
+    outfile.with_file_name(name)
+}
+
+/// Parses dot/dash code with the parser selected by the arguments.
+fn parse_instructions(args: &Args, code: &str) -> Vec<Instruction> {
+    let code = join_prosigns(code, args.prosign_char);
+    let ins = if args.tolerant_spacing {
+        parse_tolerant_morse_code(&code, args.gap_thresholds)
+    } else {
+        parse_morse_code(&code)
+    };
+
+    ins.into_iter()
+        .map(|is| match is {
+            Instruction::WordSpace => args.word_break(),
+            is => is,
+        })
+        .collect()
+}
+
+/// Runs the letters on either side of each `marker` together, so that
+/// `... _ -.-` is sent as the single character `...-.-`.
+fn join_prosigns(code: &str, marker: char) -> String {
+    let mut joined = String::with_capacity(code.len());
+    let mut joining = false;
+    for c in code.chars() {
+        if c == marker {
+            // Drop the whitespace before the marker as well as after it.
+            joined.truncate(joined.trim_end().len());
+            joining = true;
+        } else if !(joining && c.is_whitespace()) {
+            joined.push(c);
+            joining = false;
+        }
+    }
+
+    joined
+}
+
+fn parse_morse_code(code: &str) -> Vec<Instruction> {
+    let words = code.split('/').map(str::trim); // [.-..- .--.-, ...]
+    let number_of_words = code.split('/').count() - 1;
+    let mut res = Vec::new();
+
+    for (index, word) in words.enumerate() {
+        res.extend(parse_morse_word(word));
+
+        // We finished encoding a word. Insert a word space
+        // instruction if another word follows.
+        if index < number_of_words {
+            res.push(Instruction::WordSpace);
+        }
+    }
+
+    res
+}
+
+fn parse_morse_word(code: &str) -> Vec<Instruction> {
+    let letters = code.split(' '); // [.-..-, .--.-]
+    let letters: Vec<Vec<Instruction>> = letters
+        .into_iter()
+        .map(|word| {
+            word.chars()
+                .flat_map(TryFrom::try_from)
+                .map(Instruction::Morse)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let number_of_letters = letters.len() - 1;
+    let mut res = Vec::new();
+
+    // Iterate over each letter the user inputs.
+    for (index, letter) in letters.into_iter().enumerate() {
+        // Iterate over each morse code char of said letter.
+        // Insert the morse code instruction followed by a
+        // symbol pause if another morse code instruction
+        // follows.
+        let letter_code_len = letter.len().saturating_sub(1);
+        for (index_inner, morse_code) in letter.into_iter().enumerate() {
+            res.push(morse_code);
+
+            if index_inner < letter_code_len {
+                res.push(Instruction::SymbolSpace);
+            }
+        }
+
+        // We finished encoding an ASCII letter. Insert a
+        // letter space instruction if another morse code
+        // instruction follows.
+        if index < number_of_letters {
+            res.push(Instruction::LetterSpace);
+        }
+    }
+
+    res
+}
+
+/// Parses dot/dash code whose gaps are runs of whitespace of varying
+/// length, classifying each run by its length relative to the shortest one.
+/// If some elements are written without a gap, the shortest run is a letter
+/// space, otherwise it is a symbol space. A `/` is always a word space.
+fn parse_tolerant_morse_code(code: &str, thresholds: GapThresholds) -> Vec<Instruction> {
+    /// The gap preceding an element.
+    #[derive(Clone, Copy)]
+    enum Gap {
+        Spaces(usize),
+        Word,
+    }
+
+    let mut elements = Vec::new();
+    let mut gap = Gap::Spaces(0);
+    for c in code.chars() {
+        match c {
+            '/' => gap = Gap::Word,
+            c if c.is_whitespace() => {
+                if let Gap::Spaces(spaces) = &mut gap {
+                    *spaces += 1;
+                }
+            }
+            c => {
+                if let Ok(morse_code) = MorseCode::try_from(c) {
+                    elements.push((gap, morse_code));
+                    gap = Gap::Spaces(0);
+                }
+            }
+        }
+    }
+
+    let gaps = elements.iter().skip(1).filter_map(|(gap, _)| match gap {
+        Gap::Spaces(spaces) => Some(*spaces),
+        Gap::Word => None,
+    });
+    let letters_run_together = gaps.clone().any(|spaces| spaces == 0);
+    let shortest = gaps.filter(|spaces| *spaces > 0).min().unwrap_or(1) as f32;
+
+    let mut res = Vec::new();
+    for (index, (gap, morse_code)) in elements.into_iter().enumerate() {
+        if index > 0 {
+            res.push(match gap {
+                Gap::Word => Instruction::WordSpace,
+                Gap::Spaces(0) => Instruction::SymbolSpace,
+                Gap::Spaces(spaces) => {
+                    // Split at the gap thresholds, by default the midpoints
+                    // between the 1:3:7 unit ratios of the spaces.
+                    let ratio = spaces as f32 / shortest;
+                    if letters_run_together {
+                        if ratio < thresholds.word / 3.0 {
+                            Instruction::LetterSpace
+                        } else {
+                            Instruction::WordSpace
+                        }
+                    } else if ratio < thresholds.letter {
+                        Instruction::SymbolSpace
+                    } else if ratio < thresholds.word {
+                        Instruction::LetterSpace
+                    } else {
+                        Instruction::WordSpace
+                    }
+                }
+            });
+        }
+        res.push(Instruction::Morse(morse_code));
+    }
+
+    res
+}
+
+/// Plays the instructions, repeating them after the message gap until
+/// interrupted if looping forever. With a callsign, it is sent after the
+/// first repetition that ends at least the identification interval after
+/// the last time it started, or after the start.
+fn play_audio(args: &Args, ins: &[Instruction]) -> Result<()> {
+    let ins = args.trimmed(ins);
+    let id = args
+        .id
+        .as_ref()
+        .map(|callsign| args.trimmed(&id_instructions(args, callsign)).to_vec());
+    let (_stream, stream_handle) = OutputStream::try_default()?;
+    let sink = rodio::Sink::try_new(&stream_handle)?;
+
+    // The samples are rendered once and replayed on every repetition.
+    let render = |ins: &[Instruction]| {
+        (args.test_tone.is_none() && args.needs_rendering())
+            .then(|| render_mono(args, ins))
+            .transpose()
+    };
+    let rendered = render(ins)?;
+    let rendered_id = id.as_deref().map(render).transpose()?.flatten();
+    let play = |ins: &[Instruction], rendered: &Option<Vec<i16>>| {
+        std::thread::scope(|scope| {
+            if args.echo && args.test_tone.is_none() {
+                scope.spawn(|| echo(args, ins));
+            }
+            match rendered {
+                Some(samples) => {
+                    sink.append(SamplesBuffer::new(1, args.output_rate(), samples.clone()));
+                    sink.sleep_until_end();
+                }
+                None => play_message(args, ins, &sink),
+            }
+        })
+    };
+    let gap = Duration::from_secs_f32(args.message_gap.unwrap_or(args.timing().word_space));
+    let start = Instant::now();
+    for part in playback(args, id.is_some(), || start.elapsed()) {
+        match (part, &id) {
+            (Playback::Message, _) => play(ins, &rendered),
+            (Playback::Id, Some(id)) => play(id, &rendered_id),
+            (Playback::Id, None) => unreachable!("identification without a callsign"),
+            (Playback::Gap, _) => sleep(gap),
+        }
+    }
+
+    Ok(())
+}
+
+/// The parts of the playback of the instructions in the order they are
+/// played, which never end when looping forever. Each part is played before
+/// the next one is taken. `elapsed` is the time since playback started,
+/// which decides when the identification is due.
+fn playback<'a>(
+    args: &'a Args,
+    has_id: bool,
+    mut elapsed: impl FnMut() -> Duration + 'a,
+) -> impl Iterator<Item = Playback> + 'a {
+    let id_interval = Duration::from_secs_f32(args.id_interval);
+    let mut last_id = Duration::ZERO;
+    let mut last = None;
+    // Whether the last gap follows the identification.
+    let mut after_id = false;
+
+    std::iter::from_fn(move || {
+        let part = match last {
+            None => Playback::Message,
+            Some(Playback::Message) if !args.loop_forever => return None,
+            Some(Playback::Message | Playback::Id) => Playback::Gap,
+            Some(Playback::Gap) if has_id && !after_id => {
+                let now = elapsed();
+                if now.saturating_sub(last_id) >= id_interval {
+                    last_id = now;
+                    Playback::Id
+                } else {
+                    Playback::Message
+                }
+            }
+            Some(Playback::Gap) => Playback::Message,
+        };
+        if part != Playback::Gap {
+            after_id = part == Playback::Id;
+        }
+        last = Some(part);
+
+        Some(part)
+    })
+}
+
+/// Prints the characters of the instructions as they are played, each
+/// when its last element ends, followed by a line break at the end.
+fn echo(args: &Args, ins: &[Instruction]) {
+    let start = Instant::now();
+    let mut stdout = std::io::stdout();
+    for (seconds, character) in echo_schedule(args, ins) {
+        sleep((start + Duration::from_secs_f32(seconds)).saturating_duration_since(Instant::now()));
+        print!("{}", character);
+        let _ = stdout.flush();
+    }
+    println!();
+}
+
+/// The characters of the instructions, read back from their elements, with
+/// the time in seconds at which the last element of each ends. Word breaks
+/// are spaces at the same time as the letter before them. With a simulated
+/// operator, the times are only estimates.
+fn echo_schedule(args: &Args, ins: &[Instruction]) -> Vec<(f32, char)> {
+    let timing = args.timing();
+    let mut fist = args.fist();
+    let mut factor = |is_dah: bool| fist.as_mut().map_or(1.0, |fist| fist.next_factor(is_dah));
+    let mut schedule = Vec::new();
+    let mut code = String::new();
+    let (mut time, mut end) = (0.0, 0.0);
+
+    for is in ins {
+        use Instruction::*;
+
+        match is {
+            Morse(c) => {
+                let (seconds, is_dah, symbol) = match c {
+                    MorseCode::Dit => (timing.dit, false, '.'),
+                    MorseCode::Dah => (timing.dah, true, '-'),
+                };
+                time += seconds * factor(is_dah);
+                end = time;
+                code.push(symbol);
+            }
+            SymbolSpace => time += timing.symbol_space * factor(false),
+            LetterSpace | WordSpace | Pause(_) => {
+                if !code.is_empty() {
+                    schedule.push((end, args.alphabet.decode(&code).unwrap_or('?')));
+                    code.clear();
+                }
+                time += match is {
+                    Pause(seconds) => *seconds,
+                    LetterSpace => timing.letter_space * factor(false),
+                    _ => timing.word_space * factor(false),
+                };
+                if !matches!(is, LetterSpace) {
+                    schedule.push((end, ' '));
+                }
+            }
+            Frequency(_) => {}
+        }
+    }
+    if !code.is_empty() {
+        schedule.push((end, args.alphabet.decode(&code).unwrap_or('?')));
+    }
+
+    schedule
+}
+
+/// Plays the instructions once by keying a tone on the sink.
+fn play_message(args: &Args, ins: &[Instruction], sink: &rodio::Sink) {
+    let timing = args.timing();
+    let mut fist = args.fist();
+    let mut duration = |seconds: f32, is_dah: bool| {
+        let factor = fist.as_mut().map_or(1.0, |fist| fist.next_factor(is_dah));
+        Duration::from_secs_f32(seconds * factor)
+    };
+
+    let mut tone = args.tone(args.frequency);
+    if let Some(seconds) = args.test_tone {
+        sink.append(tone.take_duration(Duration::from_secs_f32(seconds)));
+        sink.sleep_until_end();
+        return;
+    }
+
+    let tx_delay = Duration::from_secs_f32(args.tx_delay / 1000.0);
+    let tx_tail = Duration::from_secs_f32(args.tx_tail / 1000.0);
+    // The part of the next space that the tail of the last element took up.
+    let mut tail = Duration::ZERO;
+    for is in ins {
+        use Instruction::*;
+
+        let space = match is {
+            Morse(c) => {
+                let len = match c {
+                    MorseCode::Dit => duration(timing.dit, false),
+                    MorseCode::Dah => duration(timing.dah, true),
+                };
+                let delay = tx_delay.min(len);
+                sleep(delay);
+                sink.append(tone.clone().take_duration(len - delay + tx_tail));
+                sink.sleep_until_end();
+                tail = tx_tail;
+                continue;
+            }
+            SymbolSpace => duration(timing.symbol_space, false),
+            LetterSpace => duration(timing.letter_space, false),
+            WordSpace => duration(timing.word_space, false),
+            Pause(seconds) => Duration::from_secs_f32(*seconds),
+            Frequency(frequency) => {
+                tone = args.tone(*frequency);
+                continue;
+            }
+        };
+        sleep(space.saturating_sub(std::mem::take(&mut tail)));
+    }
+}
+
+/// Plays back frames that have already been rendered.
+fn play_frames(args: &Args, frames: Vec<i16>) -> Result<()> {
+    let (_stream, stream_handle) = OutputStream::try_default()?;
+    let sink = rodio::Sink::try_new(&stream_handle)?;
+
+    sink.append(SamplesBuffer::new(args.channels, args.file_rate(), frames));
+    sink.sleep_until_end();
+
+    Ok(())
+}
+
+/// Renders the instructions to a file and returns the interleaved frames
+/// that were written.
+fn render_audio(args: &Args, ins: &[Instruction], path: &Path) -> Result<Vec<i16>> {
+    let frames = render_to_samples(ins, &RenderConfig::new(args)?)?;
+    write_frames(args, &frames, path)?;
+
+    Ok(frames)
+}
+
+/// Renders the instructions in memory into the interleaved frames of a
+/// file, with every channel, mix, and conversion of the output applied.
+/// Rendering can still fail, as loudness normalization, resampling, and
+/// mixing in a background of another channel count can.
+pub fn render_to_samples(ins: &[Instruction], config: &RenderConfig) -> Result<Vec<i16>> {
+    let args = config.args;
+    let channels = args.channels;
+    let (samples, open) = render_gated(args, ins)?;
+    let mut frames = Vec::with_capacity(samples.len() * channels as usize);
+
+    // Every channel is a copy of the mono signal, except that the right
+    // channel is shifted by the stereo delay. The output is extended so that
+    // the right channel isn't cut off.
+    let delay = if channels >= 2 {
+        (args.output_rate() as f32 * args.stereo_delay / 1000.0) as usize
+    } else {
+        0
+    };
+    let sample = |index: Option<usize>| {
+        index
+            .and_then(|index| samples.get(index))
+            .copied()
+            .unwrap_or(0)
+    };
+    for index in 0..samples.len() + delay {
+        for channel in 0..channels {
+            frames.push(match channel {
+                1 => sample(index.checked_sub(delay)),
+                _ => sample(Some(index)),
+            });
+        }
+    }
+    if let Some(background) = &config.background {
+        background.mix(&mut frames, channels)?;
+    }
+    if let Some(open) = &open {
+        let is_open = |index: Option<usize>| index.is_some_and(|index| open.get(index) == Some(&true));
+        for (index, frame) in frames.chunks_mut(channels as usize).enumerate() {
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let open = match channel {
+                    1 => is_open(index.checked_sub(delay)),
+                    _ => is_open(Some(index)),
+                };
+                if !open {
+                    *sample = 0;
+                }
+            }
+        }
+    }
+    // The overlay comes after the gate, so that the recording is heard
+    // between the elements.
+    if let Some(overlay) = &config.overlay {
+        overlay.overlay(&mut frames, channels)?;
+    }
+    if let Some(rate) = args.resample_output {
+        frames = effects::resample(&frames, channels as usize, args.output_rate(), rate)?;
+    }
+    // The padding comes last, so that it stays silent.
+    if let Some(align) = args.align {
+        let len = frames.len() / channels as usize;
+        frames.resize(len.next_multiple_of(align) * channels as usize, 0);
+    }
+
+    Ok(frames)
+}
+
+/// Writes interleaved frames to a file in the output format.
+fn write_frames(args: &Args, frames: &[i16], path: &Path) -> Result<()> {
+    match args
+        .output_format
+        .unwrap_or_else(|| OutputFormat::from_path(path))
+    {
+        OutputFormat::Wav => {
+            let spec = hound::WavSpec {
+                channels: args.channels,
+                sample_rate: args.file_rate(),
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let mut writer = hound::WavWriter::create(path, spec)?;
+            for sample in frames {
+                writer.write_sample(*sample)?;
+            }
+            writer.finalize()?;
+        }
+        OutputFormat::Raw => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            for sample in frames {
+                writer.write_all(&sample.to_le_bytes())?;
+            }
+            writer.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders the instructions into mono samples, with the effects applied.
+fn render_mono(args: &Args, ins: &[Instruction]) -> Result<Vec<i16>> {
+    Ok(render_gated(args, ins)?.0)
+}
+
+/// Renders the instructions like [`render_mono`]. With a gate, the samples
+/// are silenced wherever the level of the tone is below it, and the gate's
+/// state is returned for every sample, so that anything mixed in later can
+/// be silenced as well.
+fn render_gated(args: &Args, ins: &[Instruction]) -> Result<(Vec<i16>, Option<Vec<bool>>)> {
+    let sample_rate = args.output_rate();
+    let (mut samples, levels): (Vec<_>, Vec<_>) =
+        render_samples(args, args.trimmed(ins)).into_iter().unzip();
+    if let Some(slope) = args.pre_emphasis {
+        samples = effects::pre_emphasis(&samples, slope, sample_rate);
+    }
+    if let Some(amount) = args.reverb {
+        samples = effects::reverb(&samples, amount, sample_rate);
+    }
+    if args.no_trailing_gap {
+        let end = samples.iter().rposition(|sample| *sample != 0).map_or(0, |index| index + 1);
+        samples.truncate(end);
+    }
+    if let Some(lufs) = args.target_lufs {
+        samples = effects::normalize_loudness(&samples, lufs, sample_rate)?;
+    }
+    let samples_of = |milliseconds: f32| (sample_rate as f32 * milliseconds / 1000.0) as usize;
+    effects::fade(&mut samples, samples_of(args.fade_in), samples_of(args.fade_out));
+
+    // The gate closes after the last tone, such as during the reverb's tail.
+    let open = args.gate.map(|db| {
+        let threshold = 10f32.powf(db / 20.0);
+        (0..samples.len())
+            .map(|index| levels.get(index).is_some_and(|level| *level >= threshold))
+            .collect::<Vec<_>>()
+    });
+    if let Some(open) = &open {
+        for (sample, open) in samples.iter_mut().zip(open) {
+            if !open {
+                *sample = 0;
+            }
+        }
+    }
+
+    Ok((samples, open))
+}
+
+/// Renders the instructions into a buffer of mono samples, each with the
+/// level of the tone, without the waveform, from 0 to 1. The test tone, if
+/// requested, replaces the instructions.
+fn render_samples(args: &Args, ins: &[Instruction]) -> Vec<(i16, f32)> {
+    /// Appends a tone, continuing at the given phase so that back-to-back
+    /// tones join without a discontinuity. The `gain` scales the volume.
+    /// The envelope doesn't fall below the key-up level, so that a tone
+    /// rises from a leaking key-up tone rather than from silence.
+    fn tone(
+        buffer: &mut Vec<(i16, f32)>,
+        phase: &mut f32,
+        samples: u64,
+        frequency: f32,
+        gain: f32,
+        args: &Args,
+    ) {
+        let step = frequency * 2.0 * PI / args.output_rate() as f32;
+        for index in 0..samples {
+            let envelope = args.envelope.gain(index, samples, args.output_rate())
+                * args.envelope_curve.as_ref().map_or(1.0, |curve| curve.gain(index, samples));
+            let level = envelope.max(args.key_up_level) * args.volume * gain;
+            let sample = args.waveform.sample(*phase, args.duty) * level * i16::MAX as f32;
+            buffer.push((sample.clamp(i16::MIN as f32, i16::MAX as f32) as i16, level));
+            *phase = (*phase + step) % (2.0 * PI);
+        }
+    }
+
+    /// Appends silence, or the tone at the key-up level if the key leaks.
+    /// The leak has no envelope and keeps the phase running, so that the
+    /// next tone continues it. After true silence, the next tone starts over
+    /// at a phase of zero.
+    fn silence(
+        buffer: &mut Vec<(i16, f32)>,
+        phase: &mut f32,
+        samples: u64,
+        frequency: f32,
+        args: &Args,
+    ) {
+        if args.key_up_level > 0.0 {
+            let step = frequency * 2.0 * PI / args.output_rate() as f32;
+            let level = args.key_up_level * args.volume;
+            for _ in 0..samples {
+                let sample = args.waveform.sample(*phase, args.duty) * level * i16::MAX as f32;
+                buffer.push((sample.clamp(i16::MIN as f32, i16::MAX as f32) as i16, level));
+                *phase = (*phase + step) % (2.0 * PI);
+            }
+        } else if samples > 0 {
+            buffer.extend((0..samples).map(|_| (0, 0.0)));
+            *phase = 0.0;
+        }
+    }
+
+    /// Appends a tone if the key is down and silence if it is up, or the
+    /// other way around with inverted keying.
+    fn key(
+        buffer: &mut Vec<(i16, f32)>,
+        phase: &mut f32,
+        samples: u64,
+        down: bool,
+        frequency: f32,
+        gain: f32,
+        args: &Args,
+    ) {
+        if down != args.invert {
+            tone(buffer, phase, samples, frequency, gain, args);
+        } else {
+            silence(buffer, phase, samples, frequency, args);
+        }
+    }
+
+    /// Appends an element of `len` samples and as much of the `space` after
+    /// it as its transmitter tail reaches into. The tone starts late by the
+    /// transmitter delay, at the element's start phase unless it continues a
+    /// tone, such as a leaking key-up tone or a transmitter tail that filled
+    /// the space before it. Returns the rest of the space.
+    fn element(
+        buffer: &mut Vec<(i16, f32)>,
+        phase: &mut f32,
+        len: u64,
+        (frequency, gain, start): (f32, f32, f32),
+        space: u64,
+        args: &Args,
+    ) -> u64 {
+        let delay = ((args.output_rate() as f32 * args.tx_delay / 1000.0) as u64).min(len);
+        let tail = ((args.output_rate() as f32 * args.tx_tail / 1000.0) as u64).min(space);
+        key(buffer, phase, delay, false, frequency, 1.0, args);
+        if buffer.last().is_none_or(|(_, level)| *level == 0.0) {
+            *phase = start;
+        }
+        key(buffer, phase, len - delay + tail, true, frequency, gain, args);
+        space - tail
+    }
+
+    let mut buffer = Vec::new();
+    let mut phase = 0.0;
+    if let Some(seconds) = args.test_tone {
+        let samples = (args.output_rate() as f32 * seconds) as u64;
+        tone(&mut buffer, &mut phase, samples, args.frequency, 1.0, args);
+        return buffer;
+    }
+
+    let timing = args.timing();
+    let mut fist = args.fist();
+    // When fitting a duration, the fractions of samples are carried over,
+    // so that the rendered length doesn't drift from the fitted timing.
+    let mut fraction = 0.0;
+    let mut samples = |seconds: f32, is_dah: bool| {
+        let factor = fist.as_mut().map_or(1.0, |fist| fist.next_factor(is_dah));
+        if args.fit_duration.is_none() {
+            return (args.output_rate() as f32 * seconds * factor) as u64;
+        }
+        let exact = args.output_rate() as f64 * seconds as f64 * factor as f64 + fraction;
+        let samples = exact.round().max(0.0);
+        fraction = exact - samples;
+        samples as u64
+    };
+    let mut frequency = args.frequency;
+    let mut rng = args.phase_randomize.then(|| args.rng());
+    // The index of the next element within its letter, for the accent.
+    let mut position = 0;
+    // An element is only rendered once the space after it is known, as its
+    // tail may reach into that space.
+    let mut pending: Option<(u64, (f32, f32, f32))> = None;
+
+    for is in ins {
+        use Instruction::*;
+
+        if matches!(is, LetterSpace | WordSpace | Pause(_)) {
+            position = 0;
+        }
+        let space = match is {
+            Morse(c) => {
+                // Exact sample counts aren't rounded from seconds.
+                let len = match c {
+                    MorseCode::Dit => args.dit_samples.unwrap_or_else(|| samples(timing.dit, false)),
+                    MorseCode::Dah => args.dah_samples.unwrap_or_else(|| samples(timing.dah, true)),
+                };
+                let gain = args.accent.as_ref().map_or(1.0, |accent| accent.gain(position));
+                let start = rng.as_mut().map_or(0.0, |rng| rng.gen_range(0.0..2.0 * PI));
+                position += 1;
+                if let Some((len, voice)) = pending.replace((len, (frequency, gain, start))) {
+                    element(&mut buffer, &mut phase, len, voice, 0, args);
+                }
+                continue;
+            }
+            SymbolSpace => samples(timing.symbol_space, false),
+            LetterSpace => samples(timing.letter_space, false),
+            WordSpace => samples(timing.word_space, false),
+            Pause(seconds) => (args.output_rate() as f32 * seconds) as u64,
+            Frequency(new_frequency) => {
+                frequency = *new_frequency;
+                continue;
+            }
+        };
+        let space = match pending.take() {
+            Some((len, voice)) => element(&mut buffer, &mut phase, len, voice, space, args),
+            None => space,
+        };
+        key(&mut buffer, &mut phase, space, false, frequency, 1.0, args);
+    }
+    if let Some((len, voice)) = pending {
+        element(&mut buffer, &mut phase, len, voice, 0, args);
+    }
+
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses the arguments the way they are parsed from the command line.
+    fn parse(argv: &[&str]) -> Result<Args> {
+        let argv = argv.iter().map(std::ffi::OsString::from).collect();
+        parse_args_from(pico_args::Arguments::from_vec(argv))
+    }
+    /// Parses valid arguments.
+    fn args(argv: &[&str]) -> Args {
+        parse(argv).unwrap()
+    }
+
+    /// The instructions of the messages of the arguments.
+    fn instructions(args: &Args) -> Vec<Instruction> {
+        concat_messages(args, &message_codes(args, &messages(args).unwrap()))
+    }
+
+    /// Writes the elements and spaces of the instructions as dot/dash code,
+    /// with symbol spaces as `_`.
+    fn written(ins: &[Instruction]) -> String {
+        ins.iter()
+            .map(|is| match is {
+                Instruction::Morse(MorseCode::Dit) => ".",
+                Instruction::Morse(MorseCode::Dah) => "-",
+                Instruction::SymbolSpace => "_",
+                Instruction::LetterSpace => " ",
+                Instruction::WordSpace => " / ",
+                Instruction::Frequency(_) | Instruction::Pause(_) => "",
+            })
+            .collect()
+    }
+
+    /// Renders the message of the arguments as the decoder reads it, with
+    /// uniform noise of up to `noise` added.
+    fn decodable(argv: &[&str], noise: f32) -> Vec<f32> {
+        let args = args(argv);
+        let mut rng = StdRng::seed_from_u64(1);
+        rendered(&args)
+            .iter()
+            .map(|sample| *sample as f32 / i16::MAX as f32 + rng.gen_range(-noise..=noise))
+            .collect()
+    }
+
+    /// A path in the temporary directory that is unique to the test run.
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("morse-{}-{}", std::process::id(), name))
+    }
+
+    /// Renders the messages of the arguments in memory, as the frames of a
+    /// file.
+    fn rendered(args: &Args) -> Vec<i16> {
+        render_to_samples(&instructions(args), &RenderConfig::new(args).unwrap()).unwrap()
+    }
+
+    /// Renders the messages of the arguments to a file named `name` in the
+    /// temporary directory and returns its path.
+    fn recording(args: &Args, name: &str) -> PathBuf {
+        let path = temp_path(name);
+        render_audio(args, &instructions(args), &path).unwrap();
+        path
+    }
+
+    /// The largest magnitude of the samples.
+    fn peak(samples: &[i16]) -> u16 {
+        samples.iter().map(|sample| sample.unsigned_abs()).max().unwrap_or(0)
+    }
+
+    /// Writes a mono recording at the default sample rate.
+    fn write_recording(path: &Path, samples: &[i16]) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for sample in samples {
+            writer.write_sample(*sample).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn draws_groups_from_the_letters_of_the_alphabet() {
+        let args = args(&["--alphabet", "greek", "--groups", "3"]);
+        assert!(args.charset.contains('Ω') && args.charset.contains('7'));
+        assert!(!args.charset.contains('W'));
+    }
+
+    #[test]
+    fn classifies_argument_errors() {
+        let usage = parse(&["-w", "100", "-t", "e"]).err().unwrap();
+        assert!(matches!(ExitStatus::of(&usage), ExitStatus::Usage));
+        let flag = parse(&["-w", "heavy", "-t", "e"]).err().unwrap();
+        assert!(matches!(ExitStatus::of(&flag), ExitStatus::Usage));
+
+        let missing = std::env::temp_dir().join("morse-missing-profile.toml");
+        let missing = missing.to_str().unwrap();
+        let io = parse(&["--config", missing, "--profile", "qso", "-t", "e"]).err().unwrap();
+        assert!(matches!(ExitStatus::of(&io), ExitStatus::Io));
+    }
+
+    #[test]
+    fn rejects_a_negative_or_infinite_stereo_delay() {
+        for delay in ["-1", "NaN", "inf"] {
+            assert!(parse(&["--stereo-delay", delay, "-t", "e"]).is_err(), "{}", delay);
+        }
+        assert!(parse(&["--stereo-delay", "3", "-t", "e"]).is_ok());
+    }
+
+    #[test]
+    fn rejects_a_test_tone_that_is_not_positive_and_finite() {
+        for seconds in ["0", "-1", "NaN", "inf"] {
+            assert!(parse(&["--test-tone", seconds]).is_err(), "{}", seconds);
+        }
+        assert!(parse(&["--test-tone", "2"]).is_ok());
+    }
+
+    #[test]
+    fn rejects_a_negative_or_infinite_transmitter_delay_and_tail() {
+        for option in ["--tx-delay", "--tx-tail"] {
+            for ms in ["-1", "NaN", "inf"] {
+                assert!(parse(&[option, ms, "-t", "e"]).is_err(), "{} {}", option, ms);
+            }
+            assert!(parse(&[option, "5", "-t", "e"]).is_ok());
+        }
+    }
+
+    #[test]
+    fn writes_the_output_format_regardless_of_the_extension() {
+        let args = args(&["--output-format", "wav", "-t", "e"]);
+        let path = recording(&args, "forced.dat");
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        let (spec, len) = (reader.spec(), reader.len());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(spec.sample_rate, SAMPLE_RATE);
+        assert!(len > 0);
+    }
+
+    #[test]
+    fn rejects_compressed_output_formats() {
+        for format in ["mp3", "FLAC"] {
+            let error = parse(&["--output-format", format, "-t", "e"]).err().unwrap();
+            assert!(format!("{:#}", error).contains("not supported in this build"), "{:#}", error);
+        }
+    }
+
+    #[test]
+    fn continues_the_phase_within_a_tone_and_restarts_it_after_a_silence() {
+        // A dit of 10 ms at 100 Hz is a single, whole period.
+        let args = args(&["-u", "0.01", "-f", "100", "..."]);
+        let samples = rendered(&args);
+
+        let step = 2.0 * PI * 100.0 / SAMPLE_RATE as f32 * i16::MAX as f32;
+        for pair in samples.windows(2) {
+            assert!((pair[1] as f32 - pair[0] as f32).abs() <= step * 1.05, "{:?}", pair);
+        }
+        assert_eq!(samples[2 * 441], 0);
+    }
+
+    #[test]
+    fn skips_batch_files_that_already_exist() {
+        let dir = temp_path("batch");
+        std::fs::create_dir_all(&dir).unwrap();
+        let batch = dir.join("batch.txt");
+        std::fs::write(&batch, "e\nt\ni\n").unwrap();
+        std::fs::write(dir.join("out_2.wav"), "kept").unwrap();
+
+        let outfile = dir.join("out.wav");
+        let argv = ["-t", "--batch", batch.to_str().unwrap(), "-o", outfile.to_str().unwrap()];
+        render_batch(&args(&argv), &batch).unwrap();
+
+        let kept = std::fs::read_to_string(dir.join("out_2.wav")).unwrap();
+        let rendered = [1, 3].map(|index| dir.join(format!("out_{}.wav", index)).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(kept, "kept");
+        assert_eq!(rendered, [true, true]);
+    }
+
+    #[test]
+    fn scores_a_clean_decode_higher_than_a_noisy_one() {
+        let options = args(&["e"]).decode_options();
+        let confidence = |noise: f32| {
+            let samples = decodable(&["-t", "-u", "0.06", "paris paris"], noise);
+            let characters = decode::decode(&samples, SAMPLE_RATE, &options);
+            let letters = characters.iter().filter(|character| !character.code.is_empty());
+            letters.clone().map(|character| character.confidence).sum::<f32>()
+                / letters.count() as f32
+        };
+        let (clean, noisy) = (confidence(0.0), confidence(2.0));
+        assert!(clean > 90.0, "{}", clean);
+        assert!(noisy < clean - 5.0, "{} {}", noisy, clean);
+    }
+
+    #[test]
+    fn parses_irregular_spacing_by_relative_length() {
+        let gaps = args(&["e"]).gap_thresholds;
+        let ins = parse_tolerant_morse_code(".  -      -  .               .  .", gaps);
+        assert_eq!(written(&ins), "._- -_. / ._.");
+        let ins = parse_tolerant_morse_code(".-   -...", gaps);
+        assert_eq!(written(&ins), "._- -_._._.");
+    }
+
+    #[test]
+    fn writes_the_outfile_before_playing_it() {
+        let path = temp_path("played.wav");
+        let args = args(&["--play", "-o", path.to_str().unwrap(), "-t", "e"]);
+        let frames = render_audio(&args, &instructions(&args), &path).unwrap();
+        // Without an audio device, playing fails.
+        let _ = play_frames(&args, frames);
+
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn decodes_next_to_an_interfering_tone_with_a_narrow_bandwidth() {
+        let samples = decodable(&["-t", "-f", "700", "paris paris"], 0.0)
+            .iter()
+            .enumerate()
+            .map(|(index, sample)| {
+                let time = index as f32 / SAMPLE_RATE as f32;
+                0.5 * sample + 0.4 * (2.0 * PI * 1000.0 * time).sin()
+            })
+            .collect::<Vec<_>>();
+        let decoded = |bandwidth: &str| {
+            let argv = ["-f", "700", "--decode-bandwidth", bandwidth, "e"];
+            text(&decode::decode(&samples, SAMPLE_RATE, &args(&argv).decode_options()))
+        };
+        assert_eq!(decoded("100"), "PARIS PARIS");
+        assert_ne!(decoded("1000"), "PARIS PARIS");
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn reads_the_messages_of_a_gzipped_file() {
+        use std::io::Write;
+
+        let path = temp_path("messages.txt.gz");
+        let mut encoder = flate2::write::GzEncoder::new(
+            std::fs::File::create(&path).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(b"cq cq\nde k1abc\n").unwrap();
+        encoder.finish().unwrap();
+
+        let text = read_infile(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text, "cq cq\nde k1abc\n");
+    }
+
+    #[test]
+    fn sends_each_message_on_a_seeded_frequency_in_the_range() {
+        let args = args(&["--freq-range", "500:900", "--seed", "7", "e"]);
+        let codes = [".-", "-...", "-.-."].map(str::to_owned);
+        let frequencies = |ins: Vec<Instruction>| {
+            ins.into_iter()
+                .filter_map(|is| match is {
+                    Instruction::Frequency(frequency) => Some(frequency),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let assigned = frequencies(concat_messages(&args, &codes));
+        assert_eq!(assigned.len(), 3);
+        assert!(assigned.iter().all(|frequency| (500.0..=900.0).contains(frequency)));
+        assert!(assigned[0] != assigned[1] && assigned[1] != assigned[2]);
+        assert_eq!(frequencies(concat_messages(&args, &codes)), assigned);
+    }
+
+    #[test]
+    fn decodes_a_padded_recording_like_the_unpadded_one() {
+        let options = args(&["e"]).decode_options();
+        let samples = decodable(&["-t", "paris paris"], 0.01);
+        let padded = [vec![0.0; 3 * SAMPLE_RATE as usize], samples.clone()].concat();
+        let padded = [padded, vec![0.0; 5 * SAMPLE_RATE as usize]].concat();
+
+        let decoded = |samples: &[f32]| text(&decode::decode(samples, SAMPLE_RATE, &options));
+        assert_eq!(decoded(&samples), "PARIS PARIS");
+        assert_eq!(decoded(&padded), decoded(&samples));
+
+        // A long pause within the recording is a single word break.
+        let word = decodable(&["-t", "paris"], 0.01);
+        let paused = [word.clone(), vec![0.0; 4 * SAMPLE_RATE as usize], word].concat();
+        assert_eq!(decoded(&paused), "PARIS PARIS");
+    }
+
+    #[test]
+    fn ignores_a_blip_shorter_than_the_minimum_element() {
+        let word = decodable(&["-t", "--wpm", "20", "-f", "700", "paris"], 0.01);
+        // The first 20 ms of the first dit, in the middle of a word space.
+        let blip = word[..SAMPLE_RATE as usize * 20 / 1000].to_vec();
+        let space = vec![0.0; 7 * 2646 / 2];
+        let samples = [word.clone(), space.clone(), blip, space, word].concat();
+
+        let decoded = |argv: &[&str]| {
+            text(&decode::decode(&samples, SAMPLE_RATE, &args(argv).decode_options()))
+        };
+        assert_eq!(decoded(&["-f", "700", "--min-element-ms", "40", "e"]), "PARIS PARIS");
+        // Without it the blip is read as a dit that joins the words.
+        assert_eq!(decoded(&["-f", "700", "e"]), "PARISEPARIS");
+    }
+
+    #[test]
+    fn echoes_the_source_text_as_it_is_played() {
+        let sos = args(&["--echo", "-t", "--wpm", "20", "sos ok"]);
+        let schedule = echo_schedule(&sos, &instructions(&sos));
+
+        let echoed = schedule.iter().map(|(_, character)| character).collect::<String>();
+        assert_eq!(echoed, "SOS OK");
+        // S ends after its three dits and two symbol spaces.
+        assert!((schedule[0].0 - 5.0 * 0.06).abs() < 1e-4, "{:?}", schedule);
+        assert!(schedule.windows(2).all(|pair| pair[0].0 <= pair[1].0), "{:?}", schedule);
+        let (end, _) = schedule.last().unwrap();
+        assert!(*end <= total_duration(&sos, &instructions(&sos)) + 1e-4);
+    }
+
+    #[test]
+    fn writes_every_sample_to_all_channels() {
+        let args = args(&["--channels", "4", "-t", "-u", "0.06", "e"]);
+        let path = recording(&args, "quad.wav");
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        let (spec, len) = (reader.spec(), reader.len());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(spec.channels, 4);
+        assert_eq!(len, 4 * 2646);
+        assert!(parse(&["--channels", "9", "-t", "e"]).is_err());
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn fetches_a_message_from_a_server() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        // Answers three requests, by their path.
+        let server = std::thread::spawn(move || {
+            for stream in listener.incoming().take(3) {
+                let mut stream = stream.unwrap();
+                let mut request = String::new();
+                BufReader::new(&stream).read_line(&mut request).unwrap();
+                let (status, content_type, body) = match request.split(' ').nth(1) {
+                    Some("/message") => ("200 OK", "text/plain", "cq de k1abc"),
+                    Some("/binary") => ("200 OK", "application/octet-stream", "cq"),
+                    _ => ("404 Not Found", "text/plain", "not found"),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+                    status,
+                    content_type,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        assert_eq!(fetch_url(&format!("{}/message", base)).unwrap(), "cq de k1abc");
+        let missing = fetch_url(&format!("{}/missing", base)).err().unwrap();
+        assert!(missing.to_string().contains("404"), "{:#}", missing);
+        let binary = fetch_url(&format!("{}/binary", base)).err().unwrap();
+        assert!(binary.to_string().contains("instead of text"), "{:#}", binary);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn decodes_a_stream_as_it_arrives() {
+        let samples = decodable(&["-t", "paris paris"], 0.05);
+        let samples = [samples, vec![0.0; SAMPLE_RATE as usize]].concat();
+        let options = args(&["e"]).decode_options();
+        let mut decoder = decode::StreamDecoder::new(&options, SAMPLE_RATE);
+
+        let mut resolved = Vec::new();
+        for chunk in samples.chunks(1000) {
+            resolved.push(decoder.push(chunk));
+        }
+        resolved.push(decoder.finish());
+        assert_eq!(resolved.concat().trim(), "PARIS PARIS");
+        // The first word is resolved long before the end of the stream.
+        let first = resolved.iter().position(|text| text.contains('S')).unwrap();
+        assert!(first < resolved.len() / 2);
+    }
+
+    #[test]
+    fn derives_the_standard_timing_from_the_default_unit() {
+        let timing = args(&["e"]).timing();
+        let lengths = [
+            timing.dit,
+            timing.dah,
+            timing.symbol_space,
+            timing.letter_space,
+            timing.word_space,
+        ];
+        for (length, units) in lengths.into_iter().zip([1.0, 3.0, 1.0, 3.0, 7.0]) {
+            assert!((length - 0.3 * units).abs() < 1e-6, "{:?}", timing);
+        }
+    }
+
+    #[test]
+    fn runs_letters_joined_by_the_marker_together() {
+        assert_eq!(join_prosigns("..._-.- .- _ -.", '_'), "...-.- .--.");
+        let args = args(&["-- ..._-.- -"]);
+        assert_eq!(written(&instructions(&args)), "-_- ._._._-_._- -");
+    }
+
+    #[test]
+    fn decodes_short_dahs_with_a_lower_threshold() {
+        // Dahs of 1.6 dits, as sent by a hurried hand.
+        let argv = ["-t", "--wpm", "20", "--dah-samples", "4234", "paris paris"];
+        let samples = decodable(&argv, 0.01);
+        let decoded = |threshold: &str| {
+            let options = args(&["--dit-dah-threshold", threshold, "e"]).decode_options();
+            text(&decode::decode(&samples, SAMPLE_RATE, &options))
+        };
+        assert_ne!(decoded("2"), "PARIS PARIS");
+        assert_eq!(decoded("1.3"), "PARIS PARIS");
+    }
+
+    #[test]
+    fn lists_the_prosigns_with_their_code() {
+        let list = prosign_list();
+        assert!(list.lines().any(|line| line.starts_with("SK   ...-.-")));
+        assert!(list.lines().any(|line| line.starts_with("AR   .-.-.")));
+        assert_eq!(list.lines().count(), PROSIGNS.len());
+    }
+
+    #[test]
+    fn decodes_pcm_read_in_odd_pieces() {
+        /// A reader that returns at most 1001 bytes at a time, so reads end
+        /// in the middle of samples.
+        struct Trickle<'a>(&'a [u8]);
+
+        impl std::io::Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(self.0.len()).min(1001);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        let args = args(&["-t", "paris paris"]);
+        let pcm = rendered(&args)
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<_>>();
+        let mut output = Vec::new();
+        decode_pcm(Trickle(&pcm), SAMPLE_RATE, &args.decode_options(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap().trim(), "PARIS PARIS");
+    }
+
+    #[test]
+    fn describes_a_rendered_file_in_a_sidecar() {
+        let path = temp_path("sidecar.wav");
+        let args = args(&["--channels", "2", "-u", "0.06", "-t", "e"]);
+        let frames = render_audio(&args, &instructions(&args), &path).unwrap();
+        write_sidecar(&args, &path, ".", Some(args.frequency), &frames).unwrap();
+
+        let json = std::fs::read_to_string(path.with_extension("json")).unwrap();
+        let samples = frames.len() / 2;
+        assert!(json.contains("\"wpm\": 20,"), "{}", json);
+        let duration = samples as f64 / SAMPLE_RATE as f64;
+        assert!(json.contains(&format!("\"duration\": {},", duration)), "{}", json);
+        assert!(json.contains(&format!("\"samples\": {},", samples)), "{}", json);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.with_extension("json")).unwrap();
+    }
+
+    #[test]
+    fn takes_defaults_from_a_profile() {
+        let path = temp_path("config.toml");
+        let config = "[profiles.qrp]\nfrequency = 550\nwpm = 15\nwaveform = \"square\"\n\
+                      volume = 0.25\noutput_format = \"raw\"\n";
+        std::fs::write(&path, config).unwrap();
+        let path = path.to_str().unwrap();
+
+        let args = args(&["--config", path, "--profile", "qrp", "-f", "600", "-t", "e"]);
+        // The flag takes precedence over the profile.
+        assert_eq!(args.frequency, 600.0);
+        assert_eq!(args.unit, unit_of_wpm(15.0));
+        assert!(matches!(args.waveform, Waveform::Square));
+        assert_eq!(args.volume, 0.25);
+        assert!(matches!(args.output_format, Some(OutputFormat::Raw)));
+
+        let unknown = parse(&["--config", path, "--profile", "qso", "-t", "e"]).err().unwrap();
+        assert!(format!("{:#}", unknown).contains("no profile qso"), "{:#}", unknown);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn scales_letter_and_word_spaces_with_their_flags() {
+        let len = |argv: &[&str]| {
+            let args = args(&[&["--wpm", "20"], argv].concat());
+            rendered(&args).len()
+        };
+        let unit = 2646;
+        let letters = len(&[". ."]);
+        assert_eq!(len(&["--letter-space", "6", ". ."]), letters + 3 * unit);
+        let words = len(&[". / ."]);
+        assert_eq!(len(&["--word-space", "14", ". / ."]), words + 7 * unit);
+        // Each flag only affects its own kind of space.
+        assert_eq!(len(&["--word-space", "14", ". ."]), letters);
+        assert_eq!(len(&["--letter-space", "6", ". / ."]), words);
+    }
+
+    #[test]
+    fn exports_the_keying_as_a_haptic_pattern() {
+        let path = temp_path("haptic.json");
+        let letter = args(&["--wpm", "20", ".-"]);
+        write_haptic(&letter, &instructions(&letter), &path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[60, 60, 180]\n");
+
+        // A word space is a single off time.
+        let words = args(&["--wpm", "20", ". / ."]);
+        write_haptic(&words, &instructions(&words), &path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[60, 420, 60]\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_render_a_message_longer_than_the_maximum() {
+        let long = args(&["--max-duration", "5", "-t", "the quick brown fox"]);
+        let error = check_duration(&long, &instructions(&long)).err().unwrap();
+        assert!(error.to_string().contains("more than the maximum of 5 seconds"), "{:#}", error);
+
+        let short = args(&["--max-duration", "60", "-t", "the quick brown fox"]);
+        check_duration(&short, &instructions(&short)).unwrap();
+    }
+
+    #[test]
+    fn counts_the_words_letters_and_elements_of_a_message() {
+        let args = args(&["-t", "sos ok"]);
+        assert_eq!(counts(&instructions(&args)), (2, 5, 15));
+        assert_eq!(counts(&[Instruction::WordSpace]), (0, 0, 0));
+    }
+
+    #[test]
+    fn identifies_when_the_interval_has_passed_since_the_last_identification() {
+        use Playback::*;
+
+        let looping = args(&["--loop-forever", "--id", "call", "--id-interval", "10", "-t", "e"]);
+        let mut seconds = 0;
+        let clock = || {
+            seconds += 4;
+            Duration::from_secs(seconds)
+        };
+        let parts = playback(&looping, true, clock).take(14).collect::<Vec<_>>();
+        let expected = [Message, Gap, Message, Gap, Message, Gap, Id, Gap];
+        assert_eq!(parts[..8], expected);
+        assert_eq!(parts[8..], expected[..6]);
+        assert_eq!(playback(&looping, false, || Duration::MAX).nth(2), Some(Message));
+
+        let once = args(&["-t", "e"]);
+        assert_eq!(playback(&once, true, || Duration::MAX).count(), 1);
+    }
+
+    #[test]
+    fn a_ramp_clicks_less_than_a_hard_edge() {
+        let (hard, _) = clicks(&args(&["--click-test"])).unwrap();
+        let (ramped, steady) = clicks(&args(&["--click-test", "--sidetone-shape", "soft"])).unwrap();
+        assert!(ramped < hard / 2.0, "{} {}", ramped, hard);
+        assert!(ramped <= steady);
+    }
+
+    #[test]
+    fn renders_each_word_to_its_own_file() {
+        let path = temp_path("words.wav");
+        let words = args(&["--split-words", "-o", path.to_str().unwrap(), "-t", "cq de k1abc"]);
+        assert_eq!(split_words(&instructions(&words)).len(), 3);
+
+        let codes = message_codes(&words, &messages(&words).unwrap());
+        send(&words, &codes, &instructions(&words)).unwrap();
+        for index in 1..=3 {
+            let part = batch_path(&path, index);
+            assert!(part.exists(), "{}", part.display());
+            std::fs::remove_file(part).unwrap();
+        }
+        assert!(!batch_path(&path, 4).exists());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn passes_the_round_trip_self_test() {
+        self_test(&args(&["--self-test"])).unwrap();
+        self_test(&args(&["--self-test", "--wpm", "30", "--sidetone-shape", "soft"])).unwrap();
+    }
+
+    #[test]
+    fn sends_letters_at_the_pitch_of_the_table() {
+        let path = temp_path("pitches.txt");
+        std::fs::write(&path, "# a fifth apart\ne 440\nT 660\n").unwrap();
+        let pitches = read_pitch_table(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let args = args(&["--wpm", "20", "-t", "et"]);
+        let morse_code = concat_pitched(&args, &messages(&args).unwrap(), &pitches);
+        let samples = render_mono(&args, &morse_code).unwrap();
+        // The frequency of a tone from the number of its zero crossings.
+        let frequency = |tone: &[i16]| {
+            let signs = tone.iter().filter(|sample| **sample != 0).map(|sample| *sample > 0);
+            let crossings = signs.clone().zip(signs.skip(1)).filter(|(a, b)| a != b).count();
+            crossings as f32 / 2.0 * SAMPLE_RATE as f32 / tone.len() as f32
+        };
+        let unit = 2646;
+        assert!((frequency(&samples[..unit]) - 440.0).abs() < 10.0);
+        assert!((frequency(&samples[4 * unit..6 * unit]) - 660.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn ends_on_the_last_tone_without_a_trailing_gap() {
+        let render = |argv: &[&str]| {
+            let args = args(&[&["--wpm", "20"], argv].concat());
+            rendered(&args)
+        };
+        let padded = render(&[". / . /"]);
+        let trimmed = render(&["--no-trailing-gap", ". / . /"]);
+        assert!(padded.len() > trimmed.len());
+        assert_eq!(trimmed.len(), 2646 + 7 * 2646 + 2646);
+        assert_eq!(trimmed[..], padded[..trimmed.len()]);
+        assert_ne!(*trimmed.last().unwrap(), 0);
+
+        // The tail of the reverb is kept until it dies out.
+        let reverb = render(&["--no-trailing-gap", "--reverb", "0.3", ". / . /"]);
+        assert!(reverb.len() > trimmed.len());
+        assert_ne!(*reverb.last().unwrap(), 0);
+    }
+
+    #[test]
+    fn measures_a_positive_render_throughput() {
+        let args = args(&["--bench", "e", "--iterations", "3", "-t"]);
+        let (average, throughput) = throughput(&args, "paris").unwrap();
+        assert!(average > 0.0);
+        assert!(throughput > 0.0 && throughput.is_finite());
+    }
+
+    #[test]
+    fn decodes_a_stereo_recording_like_the_mono_one() {
+        let decoded = |channels: &str| {
+            let args = args(&["--channels", channels, "-t", "paris paris"]);
+            let path = recording(&args, &format!("channels-{}.wav", channels));
+            let (samples, _) = decode::read_wav(&path).unwrap();
+            let characters = decode_wav(&path, &args.decode_options()).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            (samples, text(&characters))
+        };
+        let (mono, text) = decoded("1");
+        assert_eq!(text, "PARIS PARIS");
+        assert_eq!(decoded("2"), (mono, text));
+    }
+
+    #[test]
+    fn prints_the_code_of_a_text() {
+        let sos = args(&["-t", "--print-code", "SOS"]);
+        assert_eq!(printed_code(&message_codes(&sos, &messages(&sos).unwrap())), "... --- ...");
+        assert_eq!(printed_code(&["...".into(), "---".into()]), "... / ---");
+    }
+
+    #[test]
+    fn renders_a_flashcard_for_every_letter_and_digit() {
+        let dir = temp_path("flashcards");
+        let args = args(&["--wpm", "40", "--flashcards", dir.to_str().unwrap()]);
+        render_flashcards(&args, &dir).unwrap();
+
+        let manifest = std::fs::read_to_string(dir.join("manifest.json")).unwrap();
+        for (character, code) in [("A", ".-"), ("0", "-----")] {
+            let file = format!("{}.wav", character);
+            assert!(dir.join(&file).exists());
+            let entry = format!(
+                "\"{}\": {{ \"file\": \"{}\", \"code\": \"{}\" }}",
+                character, file, code
+            );
+            assert!(manifest.contains(&entry), "{}", manifest);
+        }
+        assert!(!dir.join("?.wav").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn repeats_a_message_as_often_as_it_fits() {
+        // A dit and a word space take 0.48 seconds at 20 WPM.
+        let args = args(&["--wpm", "20", "."]);
+        let repeated = |seconds: f32| repeat_until(&args, &instructions(&args), seconds);
+        let repetitions = |seconds: f32| {
+            let ins = repeated(seconds).unwrap();
+            ins.iter().filter(|is| matches!(is, Instruction::Pause(_))).count()
+        };
+        assert_eq!(repetitions(2.0), 4);
+        assert_eq!(repetitions(1.9), 3);
+        assert!((total_duration(&args, &repeated(2.0).unwrap()) - 1.92).abs() < 1e-3);
+
+        let error = repeated(0.4).err().unwrap();
+        assert!(error.to_string().contains("longer than 0.4 seconds"), "{:#}", error);
+    }
+
+    #[test]
+    fn inverted_keying_silences_the_elements() {
+        let render = |argv: &[&str]| {
+            let args = args(&[&["--wpm", "20"], argv].concat());
+            rendered(&args)
+        };
+        let (normal, inverted) = (render(&[". ."]), render(&["--invert", ". ."]));
+        assert_eq!(normal.len(), inverted.len());
+        let (dit, space) = (0..2646, 2646..4 * 2646);
+        assert!(peak(&normal[dit.clone()]) > 30_000);
+        assert_eq!(peak(&inverted[dit]), 0);
+        assert_eq!(peak(&normal[space.clone()]), 0);
+        assert!(peak(&inverted[space]) > 30_000);
+    }
+
+    #[test]
+    fn renders_elements_of_exact_sample_lengths() {
+        let argv = ["--wpm", "20", "--dit-samples", "1000", "--dah-samples", "3500", ". -"];
+        let args = args(&argv);
+        let samples = rendered(&args);
+        // The letter space keeps its length in seconds.
+        let space = (SAMPLE_RATE as f32 * args.timing().letter_space) as usize;
+        assert_eq!(samples.len(), 1000 + space + 3500);
+        assert_ne!(samples[999], 0);
+        assert_eq!(peak(&samples[1000..1000 + space]), 0);
+        assert_ne!(samples[1000 + space + 1], 0);
+    }
+
+    #[test]
+    fn transcodes_a_recording_to_another_speed() {
+        let (source, target) = (temp_path("slow.wav"), temp_path("fast.wav"));
+        let slow = args(&["--wpm", "15", "-t", "paris paris"]);
+        let slow_frames = render_audio(&slow, &instructions(&slow), &source).unwrap();
+
+        let argv = ["--transcode", source.to_str().unwrap(), "-o", target.to_str().unwrap()];
+        let fast = args(&[&argv[..], &["--wpm", "30"]].concat());
+        transcode(&fast, &source).unwrap();
+        let characters = decode_wav(&target, &fast.decode_options()).unwrap();
+        assert_eq!(text(&characters), "PARIS PARIS");
+        let fast_frames = hound::WavReader::open(&target).unwrap().len() as usize;
+        assert!(fast_frames < slow_frames.len() * 2 / 3);
+        std::fs::remove_file(&source).unwrap();
+        std::fs::remove_file(&target).unwrap();
+    }
+
+    #[test]
+    fn joins_the_positional_arguments_with_spaces() {
+        assert_eq!(args(&["....", ".."]).morse_code, ".... ..");
+        let flags = args(&["....", "--frequency", "600", "..", "-t"]);
+        assert_eq!((flags.morse_code.as_str(), flags.frequency), (".... ..", 600.0));
+        assert_eq!(written(&instructions(&args(&["....", ".."]))), "._._._. ._.");
+    }
+
+    #[test]
+    fn plans_the_resolved_frequency_and_duration() {
+        let fixed = args(&["--dry-run-json", "-f", "650", "--wpm", "20", "-t", "ee"]);
+        let json = plan(&fixed, &instructions(&fixed), Some(Path::new("out.raw"))).to_json();
+        assert!(json.contains("\"frequency\": 650,"), "{}", json);
+        let duration = json
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("\"duration\": "))
+            .and_then(|value| value.trim_end_matches(',').parse::<f32>().ok());
+        assert!(duration.is_some_and(|duration| (duration - 0.3).abs() < 1e-4), "{}", json);
+        assert!(json.contains("\"format\": \"raw\","), "{}", json);
+        assert!(json.contains("\"play\": false,"), "{}", json);
+
+        let range = args(&["--freq-range", "500:800", "-t", "e"]);
+        let json = plan(&range, &instructions(&range), None).to_json();
+        assert!(json.contains("\"frequency\": null,"), "{}", json);
+    }
+
+    #[test]
+    fn splits_a_decoded_transmission_into_overs() {
+        let samples = decodable(&["-t", "cq de k1abc <AR> r r <AR>"], 0.01);
+        let characters = decode::decode(&samples, SAMPLE_RATE, &args(&["e"]).decode_options());
+        let overs = split_overs(&characters);
+        assert_eq!(overs.lines().collect::<Vec<_>>(), ["CQ DE K1ABC <AR>", "R R <AR>"]);
+    }
+
+    #[test]
+    fn accents_the_first_element_of_each_letter() {
+        let args = args(&["--wpm", "20", "--volume", "0.5", "--accent", "1.5", "... ..."]);
+        let samples = rendered(&args);
+        // The peaks of the dits, which start every two units within a letter.
+        // The spaces may be a sample shorter than their units.
+        let unit = 2646;
+        let peaks = [0, 2, 4, 8, 10, 12].map(|start| peak(&samples[start * unit..][..unit - 10]));
+        assert!(peaks[0] as f32 > 1.4 * peaks[1] as f32, "{:?}", peaks);
+        assert_eq!(peaks[1], peaks[2]);
+        assert_eq!(peaks[3], peaks[0]);
+        assert_eq!(peaks[4..], peaks[1..3]);
+    }
+
+    #[test]
+    fn reports_unknown_characters_with_their_offsets() {
+        let text = args(&["--report-unknown", "-t", "h~ll#o"]);
+        let unknown = unknown_characters(&text, &messages(&text).unwrap());
+        assert_eq!(unknown, [(0, 1, '~'), (0, 4, '#')]);
+        assert_eq!(
+            unknown_report(&unknown),
+            "[{\"message\": 0, \"offset\": 1, \"character\": \"~\"}, \
+             {\"message\": 0, \"offset\": 4, \"character\": \"#\"}]"
+        );
+
+        // A lone x separates words, but not within a letter.
+        let code = args(&["--report-unknown", ". x -x ?"]);
+        let unknown = unknown_characters(&code, &messages(&code).unwrap());
+        assert_eq!(unknown, [(0, 5, 'x'), (0, 7, '?')]);
+    }
+
+    #[test]
+    fn playback_renders_every_sample_generator_setting() {
+        assert!(!args(&["-t", "e"]).needs_rendering());
+        for option in [
+            &["--sidetone-shape", "soft"][..],
+            &["--pre-emphasis", "6"],
+            &["--reverb", "0.3"],
+            &["--gate", "-40"],
+            &["--key-up-level", "0.1"],
+        ] {
+            let argv = [option, &["-t", "e"]].concat();
+            assert!(args(&argv).needs_rendering(), "{:?}", option);
+        }
+    }
+
+    #[test]
+    fn formats_code_in_the_canonical_form() {
+        let args = args(&["--format-code", "e"]);
+        assert_eq!(formatted_code(&args, "..  .-  /   -..."), ".. .- / -...");
+        assert_eq!(formatted_code(&args, "...._.. -"), "...... -");
+    }
+
+    #[test]
+    fn decodes_swapped_paddles_with_the_flag() {
+        // PARIS keyed with the paddles swapped.
+        let swapped = encode_text("paris", Alphabet::Latin)
+            .chars()
+            .map(|c| match c {
+                '.' => '-',
+                '-' => '.',
+                c => c,
+            })
+            .collect::<String>();
+        let samples = decodable(&[swapped.as_str()], 0.01);
+        let decoded = |argv: &[&str]| {
+            let options = args(&[argv, &["e"]].concat()).decode_options();
+            text(&decode::decode(&samples, SAMPLE_RATE, &options))
+        };
+        assert_eq!(decoded(&["--decode-swap"]), "PARIS");
+        assert_ne!(decoded(&[]), "PARIS");
+    }
+
+    #[test]
+    fn gates_the_background_between_elements() {
+        let path = temp_path("hum.wav");
+        write_recording(&path, &[1000; SAMPLE_RATE as usize]);
+
+        let render = |argv: &[&str]| {
+            let background = ["--background", path.to_str().unwrap(), "-o", "out.wav"];
+            rendered(&args(&[&background[..], &["--wpm", "20"], argv, &[". ."]].concat()))
+        };
+        let gap = 2646..4 * 2646 - 1;
+        let hum = render(&[]);
+        assert!(hum[gap.clone()].iter().all(|sample| *sample == 1000));
+        let gated = render(&["--gate", "-40"]);
+        assert!(gated[gap].iter().all(|sample| *sample == 0));
+        // The background is still heard under the tones.
+        assert_eq!(gated[..2646], hum[..2646]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn fits_the_rendered_length_to_the_duration() {
+        for (seconds, extra) in [("3", &[][..]), ("7.77", &["--dit-samples", "1000"])] {
+            let argv = [&["--fit-duration", seconds], extra, &["-t", "paris paris"]].concat();
+            let mut args = args(&argv);
+            let ins = instructions(&args);
+            let target = seconds.parse::<f32>().unwrap();
+            args.unit = fit_unit(&args, &ins, target).unwrap();
+
+            let len = render_mono(&args, &ins).unwrap().len() as f32;
+            assert!((len - target * SAMPLE_RATE as f32).abs() <= 1.0, "{} {}", seconds, len);
+        }
+    }
+
+    #[test]
+    fn plots_distinct_clusters_of_dits_and_dahs() {
+        let rendering = args(&["--wpm", "20", "-t", "paris paris"]);
+        let path = recording(&rendering, "histogram.wav");
+        let (_, histogram, _) = decode_wav_plot(&path, &rendering.decode_options()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The bins of the key-down histogram, as their lower bound in units
+        // and their count.
+        let key_down = histogram
+            .split("key-up")
+            .next()
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|row| {
+                let mut fields = row.split_whitespace();
+                let bin = fields.next().unwrap().parse::<f32>().unwrap();
+                (bin, fields.next().unwrap().parse::<usize>().unwrap())
+            })
+            .filter(|(_, count)| *count > 0)
+            .collect::<Vec<_>>();
+        let cluster = |low: f32, high: f32| -> usize {
+            key_down
+                .iter()
+                .filter(|(bin, _)| (low..high).contains(bin))
+                .map(|(_, count)| count)
+                .sum()
+        };
+        // PARIS has 10 dits and 4 dahs.
+        assert_eq!(cluster(0.5, 1.5), 20, "{}", histogram);
+        assert_eq!(cluster(2.5, 3.5), 8, "{}", histogram);
+        assert_eq!(cluster(1.5, 2.5), 0, "{}", histogram);
+    }
+
+    #[test]
+    fn identifies_after_each_interval() {
+        // The message takes 0.5 seconds with its gap, and the callsign 0.62.
+        let argv = ["--wpm", "20", "--message-gap", "0.44", "--id", "t", "--id-interval", "1.2"];
+        let args = args(&[&argv[..], &["--repeat-until", "4", "."]].concat());
+        let repeated = repeat_until(&args, &instructions(&args), 4.0).unwrap();
+        let sequence = repeated
+            .split(|is| matches!(is, Instruction::Pause(_)))
+            .map(written)
+            .collect::<Vec<_>>();
+        assert_eq!(sequence, [".", ".", ".", "-", ".", ".", "-", ""]);
+    }
+
+    #[test]
+    fn writes_one_json_entry_per_decoded_character() {
+        let samples = decodable(&["-t", "sos ok"], 0.01);
+        let characters = decode::decode(&samples, SAMPLE_RATE, &args(&["e"]).decode_options());
+        let json = decoded_json(&characters);
+
+        let entries = json
+            .lines()
+            .filter(|line| line.contains("\"character\""))
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), "SOS OK".len(), "{}", json);
+        assert!(entries[0].contains("\"character\": \"S\", \"code\": \"...\""));
+        assert!(entries[3].contains("\"character\": \" \", \"code\": \"\""));
+        let starts = entries
+            .iter()
+            .map(|entry| {
+                let start = entry.split("\"start\": ").nth(1).unwrap();
+                start.split(',').next().unwrap().parse::<f32>().unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(starts.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", starts);
+    }
+
+    #[test]
+    fn runs_all_words_together_without_word_splits() {
+        let split = args(&["... / --- / ..."]);
+        assert_eq!(written(&instructions(&split)), "._._. / -_-_- / ._._.");
+        let joined = args(&["--no-word-split", "... / --- / ..."]);
+        let ins = instructions(&joined);
+        assert!(!ins.iter().any(|is| matches!(is, Instruction::WordSpace)));
+        assert_eq!(written(&ins), "._._. -_-_- ._._.");
+    }
+
+    #[test]
+    fn previews_at_the_lower_rate() {
+        let path = temp_path("preview.wav");
+        let full = args(&["--wpm", "20", "-t", "paris"]);
+        let preview = args(&["--preview-rate", "8000", "--wpm", "20", "-t", "paris"]);
+        let full_len = rendered(&full).len();
+        let preview_len = render_audio(&preview, &instructions(&preview), &path).unwrap().len();
+
+        assert_eq!(hound::WavReader::open(&path).unwrap().spec().sample_rate, 8000);
+        // The same duration in fewer samples.
+        let expected = full_len as f32 * 8000.0 / SAMPLE_RATE as f32;
+        assert!((preview_len as f32 - expected).abs() < 10.0, "{} {}", preview_len, expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn exports_the_table_of_the_alphabet() {
+        let path = temp_path("table.json");
+        export_table(&args(&["--export-table", path.to_str().unwrap()]), &path).unwrap();
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert!(json.contains("\"A\": \".-\""), "{}", json);
+        assert!(json.contains("\"0\": \"-----\""), "{}", json);
+        assert!(json.contains("\"9\": \"----.\""), "{}", json);
+        assert!(json.contains("\"<SK>\": \"...-.-\""), "{}", json);
+
+        let cyrillic = args(&["--alphabet", "cyrillic", "--export-table", "table.json"]);
+        export_table(&cyrillic, &path).unwrap();
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert!(json.contains("\"Ж\": \"...-\""), "{}", json);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn starts_elements_at_seeded_random_phases() {
+        let render = |argv: &[&str]| {
+            let args = args(&[&["--wpm", "20"], argv, &["...."]].concat());
+            rendered(&args)
+        };
+        // The first samples of the dits, which start every two units.
+        let firsts = |samples: &[i16]| [0, 2, 4, 6].map(|start| samples[start * 2646]);
+        assert_eq!(firsts(&render(&[])), [0; 4]);
+
+        let randomized = firsts(&render(&["--phase-randomize", "--seed", "7"]));
+        assert!(randomized.windows(2).all(|pair| pair[0] != pair[1]), "{:?}", randomized);
+        assert_eq!(firsts(&render(&["--phase-randomize", "--seed", "7"])), randomized);
+        assert_ne!(firsts(&render(&["--phase-randomize", "--seed", "8"])), randomized);
+    }
+
+    #[test]
+    fn splits_a_long_message_into_chunks_that_cover_it() {
+        let path = temp_path("chunks.wav");
+        let argv = ["--max-file-seconds", "2", "-o", path.to_str().unwrap(), "--wpm", "20"];
+        let args = args(&[&argv[..], &["-t", "the quick brown fox"]].concat());
+        let ins = instructions(&args);
+        let chunks = split_chunks(&args, &ins, 2.0).unwrap();
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| total_duration(&args, chunk) <= 2.0));
+        assert_eq!(chunks.iter().map(Vec::len).sum::<usize>(), ins.len());
+        assert_eq!(written(&chunks.concat()), written(&ins));
+
+        let codes = message_codes(&args, &messages(&args).unwrap());
+        send(&args, &codes, &ins).unwrap();
+        for index in 1..=chunks.len() {
+            std::fs::remove_file(batch_path(&path, index)).unwrap();
+        }
+        assert!(!batch_path(&path, chunks.len() + 1).exists());
+    }
+
+    #[test]
+    fn pads_the_output_to_the_alignment() {
+        let render = |argv: &[&str]| rendered(&args(&[argv, &["--wpm", "20", "-t", "e"]].concat()));
+        assert_eq!(render(&[]).len(), 2646);
+        let aligned = render(&["--align", "1024"]);
+        assert_eq!(aligned.len(), 3072);
+        assert!(aligned[2646..].iter().all(|sample| *sample == 0));
+        // Frames are aligned, not samples.
+        assert_eq!(render(&["--align", "1024", "--channels", "2"]).len(), 2 * 3072);
+        assert_eq!(render(&["--align", "2646"]).len(), 2646);
+    }
+
+    #[test]
+    fn analyzes_and_grades_the_rendered_speed() {
+        for wpm in ["15", "20", "30"] {
+            let args = args(&["-t", "--wpm", wpm, "paris paris the quick brown fox"]);
+            let path = recording(&args, &format!("speed-{}.wav", wpm));
+
+            let options = args.decode_options();
+            let dit = median_dit(&path, &options).unwrap().unwrap();
+            let grade = grade_wav(&path, &options).unwrap().unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!((1.2 / dit).round() as u32, args.wpm());
+            assert_eq!(grade.unit, dit);
+        }
+    }
+
+    #[test]
+    fn analyzes_the_speed_of_a_recording_of_dahs() {
+        let args = args(&["-t", "--wpm", "20", "mo"]);
+        let path = recording(&args, "dahs.wav");
+
+        let dit = median_dit(&path, &args.decode_options()).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((1.2 / dit).round() as u32, 20);
+    }
+
+    #[test]
+    fn spaces_around_slashes_only_pad_them() {
+        let args = args(&["..  .-  /  -..."]);
+        let words = instructions(&args)
+            .iter()
+            .filter(|instruction| matches!(instruction, Instruction::WordSpace))
+            .count();
+        assert_eq!(words, 1);
+    }
+
+    #[test]
+    fn word_separators_of_the_style_are_known_characters() {
+        let codes = [".- | -... | -.-.".to_owned(), ".- x -..x".to_owned()];
+        assert!(unknown_characters(&args(&["e"]), &codes[..1]).is_empty());
+        assert_eq!(unknown_characters(&args(&["e"]), &codes[1..]), [(0, 8, 'x')]);
+    }
+
+    #[test]
+    fn abbreviates_messages_sent_at_the_pitch_of_their_characters() {
+        let args = args(&["--abbreviate", "-t", "and"]);
+        let pitches = PitchTable::from([('E', 600.0), ('S', 900.0)]);
+        let morse_code = concat_pitched(&args, &messages(&args).unwrap(), &pitches);
+        let frequencies = morse_code
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Frequency(frequency) => Some(*frequency),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(frequencies, [600.0, 900.0]);
+    }
+
+    #[test]
+    fn decodes_a_recording_at_the_detected_frequency() {
+        let rendering = args(&["-t", "-f", "730", "paris"]);
+        let path = recording(&rendering, "detected.wav");
+
+        let options = args(&["--frequency-tolerance", "60", "-f", "700", "e"]).decode_options();
+        let (characters, _, frequency) = decode_wav_plot(&path, &options).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text(&characters), "PARIS");
+        assert!((frequency - 730.0).abs() <= 5.0, "{}", frequency);
+    }
+
+    #[test]
+    fn decodes_adaptively_across_a_change_of_speed() {
+        let mut samples = Vec::new();
+        for wpm in ["15", "30"] {
+            let args = args(&["-t", "--wpm", wpm, "paris paris"]);
+            samples.extend(rendered(&args));
+            samples.extend([0; 20000]);
+        }
+        let samples = samples
+            .iter()
+            .map(|sample| *sample as f32 / i16::MAX as f32)
+            .collect::<Vec<_>>();
+
+        let options = args(&["--adaptive", "e"]).decode_options();
+        let characters = decode::decode(&samples, SAMPLE_RATE, &options);
+        assert_eq!(text(&characters), "PARIS PARIS PARIS PARIS");
+    }
+
+    #[test]
+    fn wraps_a_qso_in_the_greeting_and_sign_off() {
+        let wrapped = |argv: &[&str]| {
+            let args = args(&[&["--qso-wrap", "-t"], argv].concat());
+            let mut codes = message_codes(&args, &messages(&args).unwrap());
+            wrap_qso(&args, &mut codes);
+            printed_code(&codes)
+        };
+        let call = encode_text("K1ABC", Alphabet::Latin);
+        let greeting = encode_text("CQ CQ DE", Alphabet::Latin);
+        // AR and SK are sent as single run-together characters.
+        assert_eq!(wrapped(&["k1abc"]), format!("{} / {} / .-.-.", greeting, call));
+        let sk = wrapped(&["--sign-off", "<SK>", "k1abc"]);
+        assert_eq!(sk, format!("{} / {} / ...-.-", greeting, call));
+        assert_eq!(wrapped(&["--greeting", "", "k1abc"]), format!("{} / .-.-.", call));
+    }
+
+    #[test]
+    fn overlays_a_recording_once() {
+        let path = temp_path("overlay.wav");
+        write_recording(&path, &[500; 1000]);
+        let render = |argv: &[&str]| {
+            rendered(&args(&[argv, &["-o", "out.wav", "--wpm", "20", "-t", "e"]].concat()))
+        };
+        let plain = render(&[]);
+        let overlaid = render(&["--overlay", path.to_str().unwrap()]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(overlaid.len(), plain.len());
+        assert!((0..1000).all(|index| overlaid[index] == plain[index].saturating_add(500)));
+        assert_eq!(overlaid[1000..], plain[1000..]);
+    }
+
+    #[test]
+    fn grades_a_clean_fist_above_a_jittery_one() {
+        let quality = |argv: &[&str]| {
+            let args = args(&[argv, &["--seed", "3", "-t", "paris paris"]].concat());
+            let path = recording(&args, "fist.wav");
+            let grade = grade_wav(&path, &args.decode_options()).unwrap().unwrap();
+            std::fs::remove_file(&path).unwrap();
+            grade.quality
+        };
+        let clean = quality(&[]);
+        assert!(clean > 99.0, "{}", clean);
+        let beginner = quality(&["--operator", "beginner"]);
+        assert!(beginner < clean - 5.0, "{} {}", beginner, clean);
+    }
+
+    #[test]
+    fn grades_a_recording_of_dahs_or_of_a_single_element() {
+        for (message, kind) in [("o", "dahs"), ("e", "dits"), ("t", "dits")] {
+            let args = args(&["-t", "--wpm", "20", message]);
+            let path = recording(&args, "single.wav");
+            let grade = grade_wav(&path, &args.decode_options()).unwrap().unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(grade.kinds[0].0, kind, "{}", message);
+            assert!(grade.quality > 95.0, "{} {}", message, grade.quality);
+        }
+    }
+
+    #[test]
+    fn key_up_leak_keeps_the_phase_running() {
+        let args = args(&["--wpm", "20", "-f", "730", "--key-up-level", "0.5", "..."]);
+        let samples = rendered(&args);
+
+        // Dits and symbol spaces of 2646 samples alternate. Divided by the
+        // level of each, the samples form one continuous sine.
+        let step = 2.0 * PI * 730.0 / SAMPLE_RATE as f32 * i16::MAX as f32;
+        let normalized = samples
+            .iter()
+            .enumerate()
+            .map(|(index, sample)| match index / 2646 % 2 {
+                0 => *sample as f32,
+                _ => *sample as f32 / 0.5,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(samples.len(), 5 * 2646);
+        for pair in normalized.windows(2) {
+            assert!((pair[1] - pair[0]).abs() <= step * 1.05, "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn joins_back_to_back_tones_without_a_phase_jump() {
+        // The transmitter tail fills the symbol spaces of the prosign.
+        let args = args(&["--wpm", "20", "-f", "730", "--tx-tail", "100", "-t", "<SK>"]);
+        let samples = rendered(&args);
+
+        let step = 2.0 * PI * 730.0 / SAMPLE_RATE as f32 * i16::MAX as f32;
+        for pair in samples.windows(2) {
+            assert!((pair[1] as f32 - pair[0] as f32).abs() <= step * 1.05, "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn renders_a_dit_in_memory() {
+        let args = args(&["-t", "--wpm", "20", "e"]);
+        let samples = rendered(&args);
+
+        // A dit at 20 WPM lasts 60 ms.
+        assert_eq!(samples.len(), 2646);
+        assert!(peak(&samples) >= i16::MAX as u16 - 1);
+    }
+
+    #[test]
+    fn renders_silent_word_gaps_and_interleaved_channels() {
+        let args = args(&["-t", "--wpm", "20", "--channels", "2", "e e"]);
+        let samples = rendered(&args);
+
+        // Two dits around a word space of seven units, in two channels.
+        assert_eq!(samples.len(), 2 * 9 * 2646);
+        assert_eq!(peak(&samples[2 * 2646..2 * 8 * 2646]), 0);
+        assert!(peak(&samples[2 * 8 * 2646..]) > 30000);
+    }
+
+    #[test]
+    fn reads_the_background_before_rendering() {
+        let missing = std::env::temp_dir().join("morse-missing-background.wav");
+        let args = args(&["--background", missing.to_str().unwrap(), "-o", "e.wav", "-t", "e"]);
+        assert!(RenderConfig::new(&args).is_err());
     }
 }
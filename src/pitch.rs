@@ -0,0 +1,57 @@
+use anyhow::{bail, Result};
+use std::{collections::HashMap, path::Path};
+
+/// The frequencies, in Hz, that characters are sent at.
+pub type PitchTable = HashMap<char, f32>;
+
+/// Reads a pitch table with a character and its frequency on every line,
+/// separated by whitespace, e.g. `A 440`. Letters are matched
+/// case-insensitively. Empty lines and lines starting with `#` are ignored.
+pub fn read_pitch_table(path: &Path) -> Result<PitchTable> {
+    let content = std::fs::read_to_string(path)?;
+    let mut table = PitchTable::new();
+
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let (Some(character), Some(frequency), None) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            bail!(
+                "line {} of {}: expected <character> <frequency>",
+                number + 1,
+                path.display()
+            );
+        };
+        let mut chars = character.chars();
+        let (Some(character), None) = (chars.next(), chars.next()) else {
+            bail!(
+                "line {} of {}: {} is not a single character",
+                number + 1,
+                path.display(),
+                character
+            );
+        };
+        let frequency = match frequency.parse::<f32>() {
+            Ok(frequency) if frequency > 0.0 => frequency,
+            _ => bail!(
+                "line {} of {}: invalid frequency {}",
+                number + 1,
+                path.display(),
+                frequency
+            ),
+        };
+
+        table.extend(
+            character
+                .to_uppercase()
+                .map(|character| (character, frequency)),
+        );
+    }
+
+    Ok(table)
+}
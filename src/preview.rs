@@ -0,0 +1,78 @@
+/// The characters of the meter, from silent to full level.
+const LEVELS: &[u8] = b" .:-=+*#";
+
+/// The width of the meter if the terminal's width is unknown.
+const DEFAULT_WIDTH: usize = 80;
+
+/// The width of the terminal, as reported by the terminal standard output
+/// is written to, or otherwise by `COLUMNS`.
+pub fn terminal_width() -> usize {
+    tty_width()
+        .or_else(|| {
+            std::env::var("COLUMNS")
+                .ok()
+                .and_then(|columns| columns.parse().ok())
+        })
+        .filter(|columns| *columns > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// The width of the terminal standard output is written to, if it is one.
+#[cfg(unix)]
+fn tty_width() -> Option<usize> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only writes a `winsize` to the pointer, which is
+    // valid for the duration of the call.
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+
+    (result == 0).then_some(size.ws_col as usize)
+}
+
+/// The width of the terminal standard output is written to, which is
+/// unknown on this platform.
+#[cfg(not(unix))]
+fn tty_width() -> Option<usize> {
+    None
+}
+
+/// A row of characters showing the peak level of the samples over time.
+/// Each character stands for an equal share of the samples.
+pub fn meter(samples: &[i16], width: usize) -> String {
+    let width = width.min(samples.len());
+    (0..width)
+        .map(|column| {
+            let share =
+                &samples[column * samples.len() / width..(column + 1) * samples.len() / width];
+            let peak = share
+                .iter()
+                .map(|sample| sample.unsigned_abs())
+                .max()
+                .unwrap_or(0);
+            let level = (peak as f32 / i16::MAX as f32 * (LEVELS.len() - 1) as f32).round();
+            LEVELS[(level as usize).min(LEVELS.len() - 1)] as char
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_a_terminal_width() {
+        assert!(terminal_width() > 0);
+    }
+
+    #[test]
+    fn meters_the_peak_level_of_each_share() {
+        let samples = [[0; 10], [i16::MAX; 10], [i16::MAX / 2; 10]].concat();
+        assert_eq!(meter(&samples, 3), " #-");
+        assert_eq!(meter(&samples, 6), "  ##--");
+        assert_eq!(meter(&samples[..2], 80).len(), 2);
+    }
+}
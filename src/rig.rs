@@ -0,0 +1,52 @@
+use crate::{config::Profile, envelope::SidetoneShape, waveform::Waveform};
+use std::str::FromStr;
+
+/// Presets that sound like common setups, for a good starting point.
+#[derive(Debug, Clone, Copy)]
+pub enum Rig {
+    /// The smooth sidetone of a Kenwood transceiver.
+    KenwoodSoft,
+    /// Crisp and low-pitched, for long sessions at high speed.
+    Contest,
+    /// The keying filter of a typical transceiver at its default pitch.
+    Transceiver,
+    /// The buzz of a square wave practice oscillator.
+    PracticeOscillator,
+}
+
+impl FromStr for Rig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Rig::*;
+
+        match s.to_lowercase().as_str() {
+            "kenwood-soft" => Ok(KenwoodSoft),
+            "contest" => Ok(Contest),
+            "transceiver" => Ok(Transceiver),
+            "practice-oscillator" => Ok(PracticeOscillator),
+            _ => Err(format!("unknown rig: {}", s)),
+        }
+    }
+}
+
+impl Rig {
+    /// The settings of the preset. Settings it doesn't mention are left to
+    /// the config profile and the defaults.
+    pub fn profile(self) -> Profile {
+        let (frequency, waveform, sidetone_shape, pre_emphasis) = match self {
+            Rig::KenwoodSoft => (800.0, Waveform::Sine, SidetoneShape::Soft, None),
+            Rig::Contest => (550.0, Waveform::Sine, SidetoneShape::Hard, None),
+            Rig::Transceiver => (700.0, Waveform::Sine, SidetoneShape::Rig, None),
+            Rig::PracticeOscillator => (1000.0, Waveform::Square, SidetoneShape::Hard, Some(-2.0)),
+        };
+
+        Profile {
+            frequency: Some(frequency),
+            waveform: Some(waveform),
+            sidetone_shape: Some(sidetone_shape),
+            pre_emphasis,
+            ..Profile::default()
+        }
+    }
+}
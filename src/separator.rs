@@ -0,0 +1,166 @@
+use std::str::FromStr;
+
+/// How the words of dot/dash code are separated. Letters are always
+/// separated by whitespace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeparatorStyle {
+    Slash,
+    Pipe,
+    /// Two or more whitespace characters in a row.
+    DoubleSpace,
+    /// An `x` on its own, as in `.- x -...`.
+    X,
+}
+
+impl FromStr for SeparatorStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use SeparatorStyle::*;
+
+        match s.to_lowercase().as_str() {
+            "slash" | "/" => Ok(Slash),
+            "pipe" | "|" => Ok(Pipe),
+            "double-space" => Ok(DoubleSpace),
+            "x" => Ok(X),
+            _ => Err(format!("unknown separator style: {}", s)),
+        }
+    }
+}
+
+impl SeparatorStyle {
+    /// Picks the style the code most likely uses, which is the one that
+    /// finds the most word breaks in it. Code with a slash always uses
+    /// slashes, as they are what the parser expects, and any spaces around
+    /// them only pad them. Code without any word break is taken to be
+    /// double-spaced, which then only trims it.
+    pub fn detect(code: &str) -> Self {
+        use SeparatorStyle::*;
+
+        if code.contains('/') {
+            return Slash;
+        }
+
+        let scores = [
+            (Pipe, code.matches('|').count()),
+            (DoubleSpace, whitespace_runs(code.trim())),
+            (
+                X,
+                code.split_whitespace().filter(|token| is_x(token)).count(),
+            ),
+        ];
+
+        let mut best = (DoubleSpace, 0);
+        for (style, score) in scores {
+            if score > best.1 {
+                best = (style, score);
+            }
+        }
+
+        best.0
+    }
+
+    /// Rewrites the code so that its words are separated by `/`.
+    pub fn normalize(self, code: &str) -> String {
+        match self {
+            SeparatorStyle::Slash => code.to_owned(),
+            SeparatorStyle::Pipe => code.replace('|', "/"),
+            SeparatorStyle::DoubleSpace => {
+                let mut normalized = String::with_capacity(code.len());
+                let mut spaces = 0;
+                for c in code.trim().chars() {
+                    if c.is_whitespace() {
+                        spaces += 1;
+                        continue;
+                    }
+                    match spaces {
+                        0 => {}
+                        1 => normalized.push(' '),
+                        _ => normalized.push_str(" / "),
+                    }
+                    spaces = 0;
+                    normalized.push(c);
+                }
+
+                normalized
+            }
+            SeparatorStyle::X => code
+                .split_whitespace()
+                .map(|token| if is_x(token) { "/" } else { token })
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+
+    /// The offsets, in characters, of the characters that separate the
+    /// words of the code in this style. Whitespace isn't included.
+    pub fn separator_offsets(self, code: &str) -> Vec<usize> {
+        let chars = code.chars().collect::<Vec<_>>();
+        let spaced = |offset: Option<usize>| {
+            offset
+                .and_then(|offset| chars.get(offset))
+                .is_none_or(|c| c.is_whitespace())
+        };
+
+        (0..chars.len())
+            .filter(|&offset| match self {
+                SeparatorStyle::Slash => chars[offset] == '/',
+                SeparatorStyle::Pipe => chars[offset] == '|',
+                SeparatorStyle::DoubleSpace => false,
+                SeparatorStyle::X => {
+                    is_x(&chars[offset].to_string())
+                        && spaced(offset.checked_sub(1))
+                        && spaced(Some(offset + 1))
+                }
+            })
+            .collect()
+    }
+}
+
+/// Whether a token of the code is an `x` word break.
+fn is_x(token: &str) -> bool {
+    token.eq_ignore_ascii_case("x")
+}
+
+/// The number of runs of two or more whitespace characters.
+fn whitespace_runs(code: &str) -> usize {
+    let mut runs = 0;
+    let mut spaces = 0;
+    for c in code.chars() {
+        if c.is_whitespace() {
+            spaces += 1;
+            if spaces == 2 {
+                runs += 1;
+            }
+        } else {
+            spaces = 0;
+        }
+    }
+
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_slashes_padded_with_spaces() {
+        assert_eq!(
+            SeparatorStyle::detect("..  .-  /  -..."),
+            SeparatorStyle::Slash
+        );
+        assert_eq!(
+            SeparatorStyle::detect("..  .-  -..."),
+            SeparatorStyle::DoubleSpace
+        );
+        assert_eq!(
+            SeparatorStyle::detect(".. | .- x -..."),
+            SeparatorStyle::Pipe
+        );
+        assert_eq!(
+            SeparatorStyle::detect(" ... --- "),
+            SeparatorStyle::DoubleSpace
+        );
+    }
+}
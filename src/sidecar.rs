@@ -0,0 +1,185 @@
+use crate::{decode::Character, timing::Timing};
+use anyhow::Result;
+use std::{fmt::Write as _, path::Path};
+
+/// A description of a rendered file, written next to it as JSON.
+pub struct Metadata<'a> {
+    pub text: &'a str,
+    pub code: &'a str,
+    pub wpm: u32,
+    /// The tone's frequency in Hz, or `None` if the messages of the file
+    /// were sent at different frequencies.
+    pub frequency: Option<f32>,
+    /// The number of samples per channel.
+    pub samples: usize,
+    pub sample_rate: u32,
+}
+
+impl Metadata<'_> {
+    pub fn to_json(&self) -> String {
+        let frequency = self
+            .frequency
+            .map_or("null".to_owned(), |frequency| frequency.to_string());
+        let duration = self.samples as f64 / self.sample_rate as f64;
+
+        format!(
+            "{{\n  \"text\": {},\n  \"code\": {},\n  \"wpm\": {},\n  \"frequency\": {},\n  \
+             \"duration\": {},\n  \"samples\": {},\n  \"sample_rate\": {}\n}}\n",
+            json_string(self.text),
+            json_string(self.code),
+            self.wpm,
+            frequency,
+            duration,
+            self.samples,
+            self.sample_rate,
+        )
+    }
+
+    /// Writes the metadata of the file at `path` to the same path with a
+    /// `.json` extension.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path.with_extension("json"), self.to_json())?;
+        Ok(())
+    }
+}
+
+/// The resolved settings of an invocation, printed instead of sending.
+pub struct Plan<'a> {
+    /// The tone's frequency in Hz, or `None` with a frequency range.
+    pub frequency: Option<f32>,
+    pub timing: Timing,
+    pub outfile: Option<&'a Path>,
+    /// The format of the outfile.
+    pub format: Option<&'a str>,
+    pub play: bool,
+    /// The estimated duration in seconds.
+    pub duration: f32,
+    pub instructions: usize,
+}
+
+impl Plan<'_> {
+    pub fn to_json(&self) -> String {
+        let or_null = |value: Option<String>| value.unwrap_or("null".to_owned());
+        let Timing {
+            dit,
+            dah,
+            symbol_space,
+            letter_space,
+            word_space,
+        } = self.timing;
+
+        format!(
+            "{{\n  \"frequency\": {},\n  \"timing\": {{ \"dit\": {}, \"dah\": {}, \
+             \"symbol_space\": {}, \"letter_space\": {}, \"word_space\": {} }},\n  \
+             \"outfile\": {},\n  \"format\": {},\n  \"play\": {},\n  \"duration\": {},\n  \
+             \"instructions\": {}\n}}\n",
+            or_null(self.frequency.map(|frequency| frequency.to_string())),
+            dit,
+            dah,
+            symbol_space,
+            letter_space,
+            word_space,
+            or_null(
+                self.outfile
+                    .map(|path| json_string(&path.to_string_lossy()))
+            ),
+            or_null(self.format.map(json_string)),
+            self.play,
+            self.duration,
+            self.instructions,
+        )
+    }
+}
+
+/// A character rendered to its own file.
+pub struct Card<'a> {
+    pub character: char,
+    /// The file's name, relative to the manifest.
+    pub file: String,
+    pub code: &'a str,
+}
+
+/// A JSON object mapping each card's character to its file and code.
+pub fn manifest(cards: &[Card]) -> String {
+    let entries = cards
+        .iter()
+        .map(|card| {
+            format!(
+                "  {}: {{ \"file\": {}, \"code\": {} }}",
+                json_string(&card.character.to_string()),
+                json_string(&card.file),
+                json_string(card.code),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    format!("{{\n{}\n}}\n", entries.join(",\n"))
+}
+
+/// A JSON object mapping each character or prosign of a table to its
+/// code.
+pub fn table(entries: &[(String, &str)]) -> String {
+    let entries = entries
+        .iter()
+        .map(|(name, code)| format!("  {}: {}", json_string(name), json_string(code)))
+        .collect::<Vec<_>>();
+
+    format!("{{\n{}\n}}\n", entries.join(",\n"))
+}
+
+/// A JSON array of the characters that couldn't be sent, each given as the
+/// index of its message, its offset in characters, and the character.
+pub fn unknown_report(unknown: &[(usize, usize, char)]) -> String {
+    let entries = unknown
+        .iter()
+        .map(|(message, offset, c)| {
+            format!(
+                "{{\"message\": {}, \"offset\": {}, \"character\": {}}}",
+                message,
+                offset,
+                json_string(&c.to_string())
+            )
+        })
+        .collect::<Vec<_>>();
+
+    format!("[{}]", entries.join(", "))
+}
+
+/// A JSON array of the decoded characters, with their start and duration
+/// in seconds. Word breaks are entries with a space and an empty code.
+pub fn decoded_json(characters: &[Character]) -> String {
+    let entries = characters
+        .iter()
+        .map(|character| {
+            format!(
+                "  {{\"character\": {}, \"code\": {}, \"start\": {:.4}, \"duration\": {:.4}, \
+                 \"confidence\": {:.0}}}",
+                json_string(&character.value.to_string()),
+                json_string(&character.code),
+                character.start,
+                character.duration,
+                character.confidence,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    format!("[\n{}\n]", entries.join(",\n"))
+}
+
+/// Quotes a string as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
@@ -0,0 +1,132 @@
+use std::{
+    path::PathBuf,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The longest part of a message that `{text}` expands to.
+const TEXT_LEN: usize = 32;
+
+/// A file name pattern with `{date}`, `{index}`, `{wpm}`, and `{text}`
+/// placeholders.
+#[derive(Debug, Clone)]
+pub struct OutputTemplate(String);
+
+/// The values the placeholders of a template expand to.
+pub struct Fields<'a> {
+    /// The current date as `YYYY-MM-DD`.
+    pub date: &'a str,
+    /// The 1-based number of the file.
+    pub index: usize,
+    pub wpm: u32,
+    /// The message of the file.
+    pub text: &'a str,
+}
+
+impl FromStr for OutputTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}') else {
+                return Err(format!("unclosed placeholder in output template: {}", s));
+            };
+            let name = &rest[start + 1..start + end];
+            if !matches!(name, "date" | "index" | "wpm" | "text") {
+                return Err(format!(
+                    "unknown placeholder in output template: {{{}}}",
+                    name
+                ));
+            }
+            rest = &rest[start + end + 1..];
+        }
+
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl OutputTemplate {
+    pub fn expand(&self, fields: &Fields) -> PathBuf {
+        // Only the characters that are safe in file names are kept.
+        let text = fields
+            .text
+            .chars()
+            .take(TEXT_LEN)
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+
+        self.0
+            .replace("{date}", fields.date)
+            .replace("{index}", &fields.index.to_string())
+            .replace("{wpm}", &fields.wpm.to_string())
+            .replace("{text}", &text)
+            .into()
+    }
+}
+
+/// Today's date in UTC as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// The Gregorian date of a day counted from 1970-01-01, after Howard
+/// Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_the_placeholders() {
+        let template = "cw_{date}_{index}_{wpm}_{text}.wav"
+            .parse::<OutputTemplate>()
+            .unwrap();
+        let fields = Fields {
+            date: "2024-03-01",
+            index: 7,
+            wpm: 25,
+            text: "cq de k1abc/p",
+        };
+        assert_eq!(
+            template.expand(&fields),
+            PathBuf::from("cw_2024-03-01_7_25_cq_de_k1abc_p.wav")
+        );
+        assert!("{speed}.wav".parse::<OutputTemplate>().is_err());
+    }
+
+    #[test]
+    fn counts_dates_from_the_epoch() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+    }
+}
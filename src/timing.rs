@@ -0,0 +1,50 @@
+/// The durations, in seconds, of the elements and spaces of a message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timing {
+    pub dit: f32,
+    pub dah: f32,
+    /// The space between the elements of a letter.
+    pub symbol_space: f32,
+    /// The space between the letters of a word.
+    pub letter_space: f32,
+    /// The space between words.
+    pub word_space: f32,
+}
+
+impl Timing {
+    /// The timing for a unit of `unit` seconds, a weight in percent, and
+    /// letter and word spaces of the given number of units, which are 3 and
+    /// 7 in standard timing.
+    ///
+    /// The weight moves time from the key-up time into the key-down time of
+    /// each element, so a weight of 50% yields the standard ratios. Every
+    /// element is followed by a space, so shifting the same amount from the
+    /// space into the element keeps the overall speed unchanged.
+    pub fn new(unit: f32, weight: f32, letter_space: f32, word_space: f32) -> Self {
+        let offset = unit * (weight - 50.0) / 50.0;
+
+        Self {
+            dit: unit + offset,
+            dah: 3.0 * unit + offset,
+            symbol_space: unit - offset,
+            letter_space: letter_space * unit - offset,
+            word_space: word_space * unit - offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weight_moves_time_from_the_space_into_the_element() {
+        let standard = Timing::new(0.06, 50.0, 3.0, 7.0);
+        let heavy = Timing::new(0.06, 60.0, 3.0, 7.0);
+
+        assert!(heavy.dit > standard.dit);
+        assert!(heavy.symbol_space < standard.symbol_space);
+        let sum = |timing: Timing| timing.dit + timing.symbol_space;
+        assert!((sum(heavy) - sum(standard)).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{Instruction, MorseCode};
+
+/// The glyph of a dit, which lasts one unit.
+const DIT: char = '·';
+
+/// The glyph of a dah, repeated for each of its three units.
+const DAH: char = '─';
+
+const DIT_COLOR: &str = "\x1b[36m";
+const DAH_COLOR: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// The kinds of spaces before an element, in the order in which lines are
+/// preferably broken at them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Gap {
+    Symbol,
+    Letter,
+    Word,
+}
+
+/// An element of the timeline.
+struct Piece {
+    /// The kind of the widest space before the element, if any.
+    gap: Option<Gap>,
+    /// The number of columns of the spaces before the element.
+    gap_width: usize,
+    text: String,
+    /// The number of columns of the element, without the color codes.
+    width: usize,
+}
+
+/// Draws the instructions as a timeline with one column per unit. Dits and
+/// dahs are drawn with distinct glyphs, and optionally in distinct ANSI
+/// colors. Lines are wrapped to `width` columns, preferably between words
+/// and otherwise between letters. Elements are only wrapped apart if a
+/// letter is wider than a line.
+pub fn timeline(ins: &[Instruction], color: bool, width: usize) -> String {
+    let mut pieces = Vec::new();
+    let (mut gap, mut gap_width) = (None, 0);
+
+    for is in ins {
+        let (space, columns) = match is {
+            Instruction::Morse(morse_code) => {
+                let (glyph, units, ansi) = match morse_code {
+                    MorseCode::Dit => (DIT, 1, DIT_COLOR),
+                    MorseCode::Dah => (DAH, 3, DAH_COLOR),
+                };
+                let mut text = String::new();
+                if color {
+                    text.push_str(ansi);
+                }
+                text.extend(std::iter::repeat_n(glyph, units));
+                if color {
+                    text.push_str(RESET);
+                }
+                pieces.push(Piece {
+                    gap: gap.take(),
+                    gap_width: std::mem::take(&mut gap_width),
+                    text,
+                    width: units,
+                });
+                continue;
+            }
+            Instruction::SymbolSpace => (Gap::Symbol, 1),
+            Instruction::LetterSpace => (Gap::Letter, 3),
+            Instruction::WordSpace | Instruction::Pause(_) => (Gap::Word, 7),
+            Instruction::Frequency(_) => continue,
+        };
+        gap = gap.max(Some(space));
+        gap_width += columns;
+    }
+
+    let mut lines: Vec<Vec<Piece>> = vec![Vec::new()];
+    for piece in pieces {
+        lines.last_mut().unwrap().push(piece);
+        loop {
+            let line = lines.last_mut().unwrap();
+            // The spaces before the first element of a line aren't drawn.
+            let line_width = line
+                .iter()
+                .map(|piece| piece.gap_width + piece.width)
+                .sum::<usize>()
+                - line[0].gap_width;
+            if line_width <= width {
+                break;
+            }
+            // Break at the widest kind of space, as late as possible.
+            let Some(index) = (1..line.len()).max_by_key(|index| (line[*index].gap, *index)) else {
+                break;
+            };
+            let rest = line.split_off(index);
+            lines.push(rest);
+        }
+    }
+
+    lines
+        .iter()
+        .map(|line| {
+            let mut text = String::new();
+            for (index, piece) in line.iter().enumerate() {
+                if index > 0 {
+                    text.extend(std::iter::repeat_n(' ', piece.gap_width));
+                }
+                text.push_str(&piece.text);
+            }
+            text
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_dits_and_dahs_with_distinct_glyphs() {
+        let ins = [
+            Instruction::Morse(MorseCode::Dit),
+            Instruction::SymbolSpace,
+            Instruction::Morse(MorseCode::Dah),
+            Instruction::WordSpace,
+            Instruction::Morse(MorseCode::Dit),
+        ];
+        assert_eq!(timeline(&ins, false, 80), "· ───       ·");
+        // Lines are broken at the word space, which isn't drawn.
+        assert_eq!(timeline(&ins, false, 5), "· ───\n·");
+
+        let colored = timeline(&ins, true, 80);
+        assert!(colored.starts_with(&format!("{}·{}", DIT_COLOR, RESET)));
+        assert!(colored.contains(&format!("{}───{}", DAH_COLOR, RESET)));
+    }
+}
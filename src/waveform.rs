@@ -0,0 +1,104 @@
+use rodio::Source;
+use std::{f32::consts::PI, str::FromStr, time::Duration};
+
+/// The shape of the tone's oscillation.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Waveform {
+    #[default]
+    Sine,
+    /// A pulse wave whose share of time at the high level is the duty
+    /// cycle.
+    Square,
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Waveform::*;
+
+        match s.to_lowercase().as_str() {
+            "sine" => Ok(Sine),
+            "square" => Ok(Square),
+            _ => Err(format!("unknown waveform: {}", s)),
+        }
+    }
+}
+
+impl Waveform {
+    /// The level between -1 and 1 at `phase`, in radians between 0 and 2π.
+    /// The duty cycle only applies to square waves.
+    pub fn sample(self, phase: f32, duty: f32) -> f32 {
+        match self {
+            Waveform::Sine => phase.sin(),
+            Waveform::Square if phase < 2.0 * PI * duty => 1.0,
+            Waveform::Square => -1.0,
+        }
+    }
+}
+
+/// An endless tone for playback.
+#[derive(Debug, Clone)]
+pub struct Tone {
+    waveform: Waveform,
+    duty: f32,
+    sample_rate: u32,
+    step: f32,
+    phase: f32,
+}
+
+impl Tone {
+    pub fn new(waveform: Waveform, duty: f32, frequency: f32, sample_rate: u32) -> Self {
+        Self {
+            waveform,
+            duty,
+            sample_rate,
+            step: frequency * 2.0 * PI / sample_rate as f32,
+            phase: 0.0,
+        }
+    }
+}
+
+impl Iterator for Tone {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.waveform.sample(self.phase, self.duty);
+        self.phase = (self.phase + self.step) % (2.0 * PI);
+        Some(sample)
+    }
+}
+
+impl Source for Tone {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_wave_is_high_for_the_duty_cycle() {
+        let samples = Tone::new(Waveform::Square, 0.25, 100.0, 10_000)
+            .take(1000)
+            .collect::<Vec<_>>();
+        for period in samples.chunks(100) {
+            assert!(period[..25].iter().all(|&sample| sample == 1.0));
+            assert!(period[26..].iter().all(|&sample| sample == -1.0));
+        }
+    }
+}